@@ -0,0 +1,79 @@
+//! Frame cost of the old rectangle-per-pixel renderer against the streaming
+//! texture one, on SDL's software renderer at the default window size:
+//!
+//! ```text
+//! cargo bench --bench render --features bench
+//! ```
+
+extern crate rust_chip8;
+extern crate sdl2;
+
+use std::time::{Duration, Instant};
+
+use sdl2::hint;
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::render::Canvas;
+use sdl2::surface::Surface;
+
+use rust_chip8::display::DEFAULT_SCALE;
+use rust_chip8::modules::{draw_rects, sample_frame, TextureCanvas};
+use rust_chip8::{CHIP8_HEIGHT, CHIP8_WIDTH, SCHIP_HEIGHT, SCHIP_WIDTH};
+
+const FRAMES: u32 = 300;
+
+// A window-sized canvas showing width x height logical pixels.
+fn canvas(width: usize, height: usize) -> Canvas<Surface<'static>> {
+    let (window_width, window_height) = (
+        CHIP8_WIDTH as u32 * DEFAULT_SCALE,
+        CHIP8_HEIGHT as u32 * DEFAULT_SCALE,
+    );
+    let surface = Surface::new(window_width, window_height, PixelFormatEnum::RGB24).unwrap();
+    let mut canvas = surface.into_canvas().unwrap();
+    canvas
+        .set_logical_size(width as u32, height as u32)
+        .unwrap();
+    canvas
+}
+
+// Average time to run one frame.
+fn time<F: FnMut()>(mut frame: F) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame();
+    }
+    start.elapsed() / FRAMES
+}
+
+fn clear(canvas: &mut Canvas<Surface<'static>>) {
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+}
+
+fn main() {
+    hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+    for &(width, height) in &[(CHIP8_WIDTH, CHIP8_HEIGHT), (SCHIP_WIDTH, SCHIP_HEIGHT)] {
+        let colors = sample_frame(width, height);
+        let mut rect_canvas = canvas(width, height);
+        let rects = time(|| {
+            clear(&mut rect_canvas);
+            draw_rects(&mut rect_canvas, &colors, width);
+            rect_canvas.present();
+        });
+        let mut target = TextureCanvas::new(canvas(width, height));
+        let textured = time(|| {
+            clear(target.canvas());
+            target
+                .draw(colors.as_flattened(), width as u32, height as u32)
+                .unwrap();
+            target.canvas().present();
+        });
+        println!(
+            "{}x{}: rectangles {:?}/frame, texture {:?}/frame ({:.1}x faster)",
+            width,
+            height,
+            rects,
+            textured,
+            rects.as_secs_f64() / textured.as_secs_f64()
+        );
+    }
+}
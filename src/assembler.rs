@@ -0,0 +1,470 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use instruction::Instruction;
+use PROGRAM_START;
+use XOCHIP_MEMORY;
+
+// Deep enough for any sane project, and stops a file that includes itself.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// An assembly error, located by file name and 1-based line number.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assembles source into a ROM to be loaded at `PROGRAM_START`. Include paths
+/// are relative to the working directory.
+///
+/// The syntax is the one `disassembler` prints: the mnemonics of the
+/// `processor.rs` handlers, `label:` definitions, `name equ value` constants,
+/// `db`/`dw` data, `include "file"` and `;` comments. Numbers are decimal, or
+/// hexadecimal with a `#`, `$` or `0x` prefix, or binary with `0b`, and can be
+/// added to or subtracted from each other, labels and constants.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::default();
+    assembler.read(source, "<source>", Path::new("."), 0)?;
+    assembler.assemble()
+}
+
+/// Assembles a source file. Include paths are relative to the including file.
+pub fn assemble_file(path: &Path) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|error| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        message: error.to_string(),
+    })?;
+    let mut assembler = Assembler::default();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    assembler.read(&source, &path.display().to_string(), dir, 0)?;
+    assembler.assemble()
+}
+
+struct Line {
+    file: usize,
+    number: usize,
+    text: String,
+}
+
+enum Statement<'a> {
+    Instruction(String, &'a str),
+    Bytes(&'a str),
+    Words(&'a str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operand {
+    V(usize),
+    I,
+    Dt,
+    St,
+    K,
+    F,
+    Hf,
+    B,
+    R,
+    IndirectI,
+    Long(i64),
+    Value(i64),
+}
+
+#[derive(Default)]
+struct Assembler {
+    files: Vec<String>,
+    // Source lines with comments stripped and includes expanded.
+    lines: Vec<Line>,
+    symbols: HashMap<String, i64>,
+}
+
+impl Assembler {
+    fn read(&mut self, source: &str, file: &str, dir: &Path, depth: usize) -> Result<(), AsmError> {
+        let index = self.files.len();
+        self.files.push(file.to_string());
+        for (number, text) in source.lines().enumerate() {
+            let line = Line {
+                file: index,
+                number: number + 1,
+                text: strip_comment(text).trim().to_string(),
+            };
+            let include = match split_word(&line.text) {
+                (word, rest) if word.eq_ignore_ascii_case("include") => rest,
+                _ => {
+                    self.lines.push(line);
+                    continue;
+                }
+            };
+
+            let name = match include
+                .strip_prefix('"')
+                .and_then(|rest| rest.strip_suffix('"'))
+            {
+                Some(name) => name,
+                None => return Err(self.error(&line, "expected include \"file\"")),
+            };
+            if depth == MAX_INCLUDE_DEPTH {
+                return Err(self.error(&line, "includes are nested too deeply"));
+            }
+            let path = dir.join(name);
+            let source = fs::read_to_string(&path).map_err(|error| {
+                self.error(&line, &format!("cannot read {}: {}", path.display(), error))
+            })?;
+            let dir = path.parent().unwrap_or_else(|| Path::new("."));
+            self.read(&source, &path.display().to_string(), dir, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    fn assemble(&mut self) -> Result<Vec<u8>, AsmError> {
+        // First pass: lay out the program and define every symbol.
+        let lines = std::mem::take(&mut self.lines);
+        let mut statements = Vec::new();
+        let mut addr = PROGRAM_START as i64;
+        for line in &lines {
+            let mut text = line.text.as_str();
+            if let Some((label, rest)) = text.split_once(':') {
+                if is_identifier(label.trim()) {
+                    self.define(line, label.trim(), addr)?;
+                    text = rest.trim();
+                }
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = split_word(text);
+            let (second, value) = split_word(rest);
+            if second.eq_ignore_ascii_case("equ") {
+                let value = self
+                    .value(value)
+                    .map_err(|message| self.error(line, &message))?;
+                self.define(line, word, value)?;
+                continue;
+            }
+
+            let (statement, size) = match word.to_ascii_lowercase().as_str() {
+                "db" => (Statement::Bytes(rest), split_operands(rest).len()),
+                "dw" => (Statement::Words(rest), 2 * split_operands(rest).len()),
+                mnemonic => {
+                    let long = mnemonic == "ld"
+                        && split_operands(rest).get(1).is_some_and(|operand| {
+                            split_word(operand).0.eq_ignore_ascii_case("long")
+                        });
+                    (
+                        Statement::Instruction(mnemonic.to_string(), rest),
+                        if long { 4 } else { 2 },
+                    )
+                }
+            };
+            statements.push((line, statement));
+            addr += size as i64;
+            if addr > XOCHIP_MEMORY as i64 {
+                return Err(self.error(line, "program does not fit in memory"));
+            }
+        }
+
+        // Second pass: every label is known, so operands can be evaluated.
+        let mut rom = Vec::new();
+        for (line, statement) in statements {
+            self.emit(&mut rom, statement)
+                .map_err(|message| self.error(line, &message))?;
+        }
+        Ok(rom)
+    }
+
+    fn define(&mut self, line: &Line, name: &str, value: i64) -> Result<(), AsmError> {
+        if !is_identifier(name) || operand_keyword(name).is_some() {
+            return Err(self.error(line, &format!("invalid symbol name: {}", name)));
+        }
+        if self.symbols.insert(name.to_string(), value).is_some() {
+            return Err(self.error(line, &format!("{} is already defined", name)));
+        }
+        Ok(())
+    }
+
+    fn emit(&self, rom: &mut Vec<u8>, statement: Statement) -> Result<(), String> {
+        match statement {
+            Statement::Bytes(operands) => {
+                for operand in split_operands(operands) {
+                    rom.push(byte(self.value(operand)?)?);
+                }
+            }
+            Statement::Words(operands) => {
+                for operand in split_operands(operands) {
+                    let word = long(self.value(operand)?)?;
+                    rom.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+            Statement::Instruction(mnemonic, operands) => {
+                let (instruction, long) = self.instruction(&mnemonic, operands)?;
+                rom.extend_from_slice(&instruction.encode().to_be_bytes());
+                if let Some(long) = long {
+                    rom.extend_from_slice(&long.to_be_bytes());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the instruction and, for LD I, long, its address word.
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        text: &str,
+    ) -> Result<(Instruction, Option<u16>), String> {
+        // SAVE and LOAD take a register range, Vx - Vy.
+        let texts = match mnemonic {
+            "save" | "load" => text.split('-').map(str::trim).collect(),
+            _ => split_operands(text),
+        };
+        let operands = texts
+            .iter()
+            .map(|text| self.operand(text))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        use self::Operand::*;
+        let instruction = match (mnemonic, operands.as_slice()) {
+            ("sys", &[Value(addr)]) => Instruction::Sys(address(addr)?),
+            ("cls", []) => Instruction::Cls,
+            ("ret", []) => Instruction::Ret,
+            ("scr", []) => Instruction::ScrollRight,
+            ("scl", []) => Instruction::ScrollLeft,
+            ("exit", []) => Instruction::Exit,
+            ("low", []) => Instruction::Low,
+            ("high", []) => Instruction::High,
+            ("audio", []) => Instruction::Audio,
+            ("scd", &[Value(n)]) => Instruction::ScrollDown(nibble(n)?),
+            ("scu", &[Value(n)]) => Instruction::ScrollUp(nibble(n)?),
+            ("jp", &[Value(addr)]) => Instruction::Jump(address(addr)?),
+            ("jp", &[V(x), Value(addr)]) => {
+                let addr = address(addr)?;
+                // With the jump_uses_vx quirk, Bxnn adds Vx, so x must match.
+                if x != 0 && x != addr >> 8 {
+                    return Err(format!(
+                        "JP V{:X} needs an address in #{:X}00..#{:X}FF",
+                        x, x, x
+                    ));
+                }
+                Instruction::JumpOffset(addr)
+            }
+            ("call", &[Value(addr)]) => Instruction::Call(address(addr)?),
+            ("se", &[V(x), V(y)]) => Instruction::SkipEq(x, y),
+            ("se", &[V(x), Value(kk)]) => Instruction::SkipEqByte(x, byte(kk)?),
+            ("sne", &[V(x), V(y)]) => Instruction::SkipNe(x, y),
+            ("sne", &[V(x), Value(kk)]) => Instruction::SkipNeByte(x, byte(kk)?),
+            ("save", &[V(x), V(y)]) => Instruction::SaveRange(x, y),
+            ("load", &[V(x), V(y)]) => Instruction::LoadRange(x, y),
+            ("ld", &[V(x), V(y)]) => Instruction::Move(x, y),
+            ("ld", &[V(x), Dt]) => Instruction::GetDelay(x),
+            ("ld", &[V(x), K]) => Instruction::WaitKey(x),
+            ("ld", &[V(x), IndirectI]) => Instruction::Restore(x),
+            ("ld", &[V(x), R]) => Instruction::LoadFlags(x),
+            ("ld", &[V(x), Value(kk)]) => Instruction::LoadByte(x, byte(kk)?),
+            ("ld", &[I, Long(addr)]) => return Ok((Instruction::LoadLongI, Some(long(addr)?))),
+            ("ld", &[I, Value(addr)]) => Instruction::LoadI(address(addr)?),
+            ("ld", &[Dt, V(x)]) => Instruction::SetDelay(x),
+            ("ld", &[St, V(x)]) => Instruction::SetSound(x),
+            ("ld", &[F, V(x)]) => Instruction::Font(x),
+            ("ld", &[Hf, V(x)]) => Instruction::BigFont(x),
+            ("ld", &[B, V(x)]) => Instruction::Bcd(x),
+            ("ld", &[IndirectI, V(x)]) => Instruction::Store(x),
+            ("ld", &[R, V(x)]) => Instruction::SaveFlags(x),
+            ("add", &[V(x), V(y)]) => Instruction::Add(x, y),
+            ("add", &[V(x), Value(kk)]) => Instruction::AddByte(x, byte(kk)?),
+            ("add", &[I, V(x)]) => Instruction::AddI(x),
+            ("or", &[V(x), V(y)]) => Instruction::Or(x, y),
+            ("and", &[V(x), V(y)]) => Instruction::And(x, y),
+            ("xor", &[V(x), V(y)]) => Instruction::Xor(x, y),
+            ("sub", &[V(x), V(y)]) => Instruction::Sub(x, y),
+            ("subn", &[V(x), V(y)]) => Instruction::Subn(x, y),
+            ("shr", &[V(x)]) => Instruction::Shr(x, x),
+            ("shr", &[V(x), V(y)]) => Instruction::Shr(x, y),
+            ("shl", &[V(x)]) => Instruction::Shl(x, x),
+            ("shl", &[V(x), V(y)]) => Instruction::Shl(x, y),
+            ("rnd", &[V(x), Value(kk)]) => Instruction::Random(x, byte(kk)?),
+            ("drw", &[V(x), V(y), Value(n)]) => Instruction::Draw(x, y, nibble(n)?),
+            ("skp", &[V(x)]) => Instruction::SkipKey(x),
+            ("sknp", &[V(x)]) => Instruction::SkipNoKey(x),
+            ("plane", &[Value(n)]) => Instruction::Plane(nibble(n)?),
+            ("pitch", &[V(x)]) => Instruction::Pitch(x),
+            _ => {
+                return Err(format!(
+                    "invalid instruction: {} {}",
+                    mnemonic.to_ascii_uppercase(),
+                    text
+                ))
+            }
+        };
+        Ok((instruction, None))
+    }
+
+    fn operand(&self, text: &str) -> Result<Operand, String> {
+        if let Some(operand) = operand_keyword(text) {
+            return Ok(operand);
+        }
+        match split_word(text) {
+            (word, value) if word.eq_ignore_ascii_case("long") => {
+                Ok(Operand::Long(self.value(value)?))
+            }
+            _ => Ok(Operand::Value(self.value(text)?)),
+        }
+    }
+
+    // Evaluates a sum of numbers and symbols, such as `sprite + 5`.
+    fn value(&self, text: &str) -> Result<i64, String> {
+        let mut total = 0;
+        let mut sign = 1;
+        let mut first = true;
+        let mut rest = text.trim();
+        loop {
+            let end = rest.find(['+', '-']).unwrap_or(rest.len());
+            let term = rest[..end].trim();
+            let value = match parse_number(term) {
+                Some(value) => value,
+                // A leading sign, as in -1.
+                None if term.is_empty() && first && end < rest.len() => 0,
+                None if term.is_empty() => return Err(format!("expected a value: {}", text)),
+                None => match self.symbols.get(term) {
+                    Some(&value) => value,
+                    None => return Err(format!("undefined symbol: {}", term)),
+                },
+            };
+            total += sign * value;
+            first = false;
+            match rest[end..].chars().next() {
+                Some(op) => {
+                    sign = if op == '+' { 1 } else { -1 };
+                    rest = &rest[end + 1..];
+                }
+                None => return Ok(total),
+            }
+        }
+    }
+
+    fn error(&self, line: &Line, message: &str) -> AsmError {
+        AsmError {
+            file: self.files[line.file].clone(),
+            line: line.number,
+            message: message.to_string(),
+        }
+    }
+}
+
+fn operand_keyword(text: &str) -> Option<Operand> {
+    let lower = text.to_ascii_lowercase();
+    let operand = match lower.as_str() {
+        "i" => Operand::I,
+        "dt" => Operand::Dt,
+        "st" => Operand::St,
+        "k" => Operand::K,
+        "f" => Operand::F,
+        "hf" => Operand::Hf,
+        "b" => Operand::B,
+        "r" => Operand::R,
+        "[i]" => Operand::IndirectI,
+        _ => match lower.strip_prefix('v') {
+            Some(digit) if digit.len() == 1 => Operand::V(usize::from_str_radix(digit, 16).ok()?),
+            _ => return None,
+        },
+    };
+    Some(operand)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower
+        .strip_prefix('#')
+        .or_else(|| lower.strip_prefix('$'))
+        .or_else(|| lower.strip_prefix("0x"))
+    {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(binary) = lower.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()
+    } else if lower.starts_with(|c: char| c.is_ascii_digit()) {
+        lower.parse().ok()
+    } else {
+        None
+    }
+}
+
+fn nibble(value: i64) -> Result<usize, String> {
+    if (0..=0xF).contains(&value) {
+        Ok(value as usize)
+    } else {
+        Err(format!("{} does not fit in a nibble", value))
+    }
+}
+
+// Negative bytes are stored as two's complement, so ADD V0, -1 works.
+fn byte(value: i64) -> Result<u8, String> {
+    if (-0x80..=0xFF).contains(&value) {
+        Ok(value as u8)
+    } else {
+        Err(format!("{} does not fit in a byte", value))
+    }
+}
+
+fn address(value: i64) -> Result<usize, String> {
+    if (0..=0xFFF).contains(&value) {
+        Ok(value as usize)
+    } else {
+        Err(format!("address {} is out of range; use LD I, long", value))
+    }
+}
+
+fn long(value: i64) -> Result<u16, String> {
+    if (0..=0xFFFF).contains(&value) {
+        Ok(value as u16)
+    } else {
+        Err(format!("{} does not fit in a word", value))
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(';') {
+        Some(start) => &line[..start],
+        None => line,
+    }
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    match text.trim().split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (text.trim(), ""),
+    }
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(str::trim).collect()
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+#[path = "./assembler_test.rs"]
+mod assembler_test;
@@ -0,0 +1,118 @@
+use super::*;
+use disassembler::disassemble;
+use std::env;
+use Processor;
+use Quirks;
+
+#[test]
+fn test_encode_round_trip() {
+    for opcode in 0..=0xFFFF {
+        if let Some(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{}", instruction);
+        }
+    }
+}
+
+#[test]
+fn test_assemble() {
+    let source = "
+        SPRITE_HEIGHT equ 5
+        start:
+            LD V0, #0A          ; hexadecimal
+            ld v1, 0b0101
+            LD I, sprite
+            DRW V0, V1, SPRITE_HEIGHT
+            SHR V2
+            ADD V3, -1
+            LD I, long data + 1
+        loop: JP loop
+        sprite:
+            db $F0, 144, #90
+        data:
+            dw #1234
+    ";
+    assert_eq!(
+        assemble(source).unwrap(),
+        vec![
+            0x60, 0x0A, 0x61, 0x05, 0xA2, 0x12, 0xD0, 0x15, 0x82, 0x26, 0x73, 0xFF, 0xF0, 0x00,
+            0x02, 0x16, 0x12, 0x10, 0xF0, 0x90, 0x90, 0x12, 0x34,
+        ]
+    );
+}
+
+#[test]
+fn test_disassembly_round_trip() {
+    let rom = [
+        0x60, 0x01, 0x22, 0x0A, 0x61, 0x02, 0x12, 0x06, 0x00, 0x00, 0x70, 0x01, 0xF0, 0x55, 0x00,
+        0xEE, 0x30, 0x00, 0xF0, 0x00, 0x03, 0x00, 0x00, 0xFD, 0x12, 0x34,
+    ];
+    assert_eq!(assemble(&disassemble(&rom, PROGRAM_START)).unwrap(), rom);
+}
+
+#[test]
+fn test_runs_on_processor() {
+    let rom = assemble(
+        "
+            LD V0, 3
+            CALL double
+        done:
+            JP done
+        double:
+            ADD V0, V0
+            RET
+        ",
+    )
+    .unwrap();
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&rom);
+    for _ in 0..4 {
+        processor.tick([false; 16]).unwrap();
+    }
+    assert_eq!(processor.v()[0], 6);
+    assert_eq!(processor.pc(), 0x204);
+}
+
+#[test]
+fn test_errors() {
+    let error = |source| assemble(source).unwrap_err();
+    assert_eq!(
+        error("CLS\nJP nowhere"),
+        AsmError {
+            file: "<source>".to_string(),
+            line: 2,
+            message: "undefined symbol: nowhere".to_string(),
+        }
+    );
+    assert_eq!(error("a:\na:").message, "a is already defined");
+    assert_eq!(error("LD V0, 256").message, "256 does not fit in a byte");
+    assert_eq!(error("LD I, #1000").line, 1);
+    assert_eq!(error("DRW V0, 5").message, "invalid instruction: DRW V0, 5");
+    assert_eq!(error("vf: CLS").message, "invalid symbol name: vf");
+    assert_eq!(
+        error("include nothing.asm").message,
+        "expected include \"file\""
+    );
+}
+
+#[test]
+fn test_include() {
+    let dir = env::temp_dir().join(format!("rust-chip8-assembler-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("main.asm"),
+        "include \"lib/font.asm\"\nLD I, glyph\n",
+    )
+    .unwrap();
+    fs::write(dir.join("lib/font.asm"), "JP skip\nglyph: db #F0\nskip:\n").unwrap();
+    fs::write(dir.join("loop.asm"), "include \"loop.asm\"\n").unwrap();
+
+    let result = assemble_file(&dir.join("main.asm"));
+    let looped = assemble_file(&dir.join("loop.asm"));
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(result.unwrap(), vec![0x12, 0x03, 0xF0, 0xA2, 0x02]);
+    assert_eq!(
+        looped.unwrap_err().message,
+        "includes are nested too deeply"
+    );
+}
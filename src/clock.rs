@@ -0,0 +1,61 @@
+/// Rate at which the delay and sound timers count down.
+pub const TIMER_HZ: u32 = 60;
+
+/// Instruction rate used when none is configured.
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 700;
+
+/// Emulated time, measured in executed instructions and 60 Hz frames.
+///
+/// The clock never looks at the host's wall clock: each frame is exactly
+/// 1/60 s of emulated time, and the number of instructions in it is derived
+/// from the configured rate, so rates that don't divide evenly by 60 still
+/// average out to the right speed.
+pub struct Clock {
+    instructions_per_second: u32,
+    frames: u64,
+    cycles: u64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Clock::new(DEFAULT_INSTRUCTIONS_PER_SECOND)
+    }
+}
+
+impl Clock {
+    pub fn new(instructions_per_second: u32) -> Self {
+        Clock {
+            instructions_per_second,
+            frames: 0,
+            cycles: 0,
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u32 {
+        self.instructions_per_second
+    }
+
+    /// Total instructions elapsed since the clock started.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Total 60 Hz frames elapsed since the clock started.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Advances emulated time by one frame and returns how many
+    /// instructions should run in it.
+    pub fn next_frame(&mut self) -> u32 {
+        self.frames += 1;
+        let target = self.frames * self.instructions_per_second as u64 / TIMER_HZ as u64;
+        let cycles = target - self.cycles;
+        self.cycles = target;
+        cycles as u32
+    }
+}
+
+#[cfg(test)]
+#[path = "./clock_test.rs"]
+mod clock_test;
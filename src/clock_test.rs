@@ -0,0 +1,30 @@
+use super::*;
+
+#[test]
+fn test_even_rate() {
+    let mut clock = Clock::new(600);
+    for _ in 0..TIMER_HZ {
+        assert_eq!(clock.next_frame(), 10);
+    }
+    assert_eq!(clock.cycles(), 600);
+    assert_eq!(clock.frames(), 60);
+}
+
+#[test]
+fn test_uneven_rate_averages_out() {
+    let mut clock = Clock::new(700);
+    let mut total = 0;
+    for _ in 0..TIMER_HZ {
+        let cycles = clock.next_frame();
+        assert!(cycles == 11 || cycles == 12);
+        total += cycles;
+    }
+    assert_eq!(total, 700);
+}
+
+#[test]
+fn test_slow_rate() {
+    let mut clock = Clock::new(30);
+    let frames: Vec<u32> = (0..4).map(|_| clock.next_frame()).collect();
+    assert_eq!(frames, [0, 1, 0, 1]);
+}
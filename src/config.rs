@@ -0,0 +1,129 @@
+//! The small subset of TOML the config files are written in: `[sections]`,
+//! `key = value` lines, quoted strings (with `\"` and `\\` escapes) and lists
+//! of them, numbers, booleans and `#` comments.
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// A line, numbered from 1, that can't be parsed, and why.
+    Invalid(usize, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref error) => write!(f, "{}", error),
+            ConfigError::Invalid(line, ref message) => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(error: io::Error) -> Self {
+        ConfigError::Io(error)
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rust-chip8`, falling back to `~/.config/rust-chip8`.
+pub fn config_dir() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|config| config.join("rust-chip8"))
+}
+
+/// Drops a `#` comment, unless the `#` is inside a string ("#" is a key
+/// name).
+pub fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..index],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Parses a quoted string or a list of them: `"Q"` or `["Q", "Up"]`. The
+/// strings are scanned rather than split on commas, since "," is a key name.
+pub fn parse_strings(value: &str) -> Option<Vec<String>> {
+    let (list, mut rest) = match value.strip_prefix('[') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let mut names = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if list {
+            if let Some(after) = rest.strip_prefix(']') {
+                return Some(names).filter(|_| after.trim().is_empty());
+            }
+        }
+        let (name, after) = unquote(rest).filter(|(name, _)| !name.is_empty())?;
+        names.push(name);
+        rest = after.trim_start();
+        if !list {
+            return Some(names).filter(|_| rest.is_empty());
+        }
+        if let Some(after) = rest.strip_prefix(',') {
+            rest = after;
+        } else if !rest.starts_with(']') {
+            return None;
+        }
+    }
+}
+
+// Reads the quoted string at the start of text, undoing its escapes, and
+// returns it along with whatever follows the closing quote.
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.strip_prefix('"')?.char_indices();
+    let mut value = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            // index counts from after the opening quote.
+            '"' => return Some((value, &text[index + 2..])),
+            '\\' => match chars.next() {
+                Some((_, escaped @ ('"' | '\\'))) => value.push(escaped),
+                _ => return None,
+            },
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+/// Quotes a string the way `parse_strings` reads it back, escaping `"` and
+/// `\` with a backslash.
+pub fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Parses a single quoted string.
+pub fn parse_string(value: &str) -> Option<String> {
+    match value.strip_prefix('[') {
+        Some(_) => None,
+        None => parse_strings(value).and_then(|mut strings| strings.pop()),
+    }
+}
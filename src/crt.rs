@@ -0,0 +1,203 @@
+use display::Rgb;
+
+/// Software CRT effects, applied on the CPU to the frame after it is scaled
+/// up. Each is a strength from 0 (off) to 100.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Crt {
+    /// Darkens the bottom of each pixel row, like the gaps between a CRT's
+    /// scan lines.
+    pub scanlines: u8,
+    /// Darkens the right edge of each pixel, like an aperture grille.
+    pub grid: u8,
+    /// Pixels brighter than the background glow onto their neighbours.
+    pub bloom: u8,
+    /// Bulges the picture like the curved glass of a tube.
+    pub curvature: u8,
+}
+
+impl Crt {
+    /// What `on` gives.
+    pub const CLASSIC: Crt = Crt {
+        scanlines: 40,
+        grid: 15,
+        bloom: 30,
+        curvature: 20,
+    };
+
+    pub fn is_off(&self) -> bool {
+        *self == Crt::default()
+    }
+
+    /// Every effect at percent of its strength, up to 100.
+    pub fn scaled(&self, percent: u32) -> Crt {
+        let scale = |strength: u8| (strength as u32 * percent / 100).min(100) as u8;
+        Crt {
+            scanlines: scale(self.scanlines),
+            grid: scale(self.grid),
+            bloom: scale(self.bloom),
+            curvature: scale(self.curvature),
+        }
+    }
+
+    /// Parses `off`, `on` or a comma-separated list of effects and strengths:
+    /// `scanlines=40,bloom=30`. Effects left out are off.
+    pub fn parse(text: &str) -> Option<Crt> {
+        match text {
+            "off" => return Some(Crt::default()),
+            "on" => return Some(Crt::CLASSIC),
+            _ => {}
+        }
+        let mut crt = Crt::default();
+        for effect in text.split(',') {
+            let (name, strength) = effect.split_once('=')?;
+            let strength = strength.trim().parse().ok().filter(|&n| n <= 100)?;
+            match name.trim() {
+                "scanlines" => crt.scanlines = strength,
+                "grid" => crt.grid = strength,
+                "bloom" => crt.bloom = strength,
+                "curvature" => crt.curvature = strength,
+                _ => return None,
+            }
+        }
+        Some(crt)
+    }
+}
+
+/// Renders frames through a `Crt` into RGB24 pixels, `scale` times the size
+/// of the frame. The curvature mapping is worked out once per size.
+pub struct CrtRenderer {
+    crt: Crt,
+    // Frame width, height and scale the map was made for.
+    size: (usize, usize, usize),
+    // For each output pixel, the scaled-up pixel it shows, or None outside
+    // the curved picture.
+    map: Vec<Option<(usize, usize)>>,
+    // Brightness of each row and column within a scaled-up pixel, out of 256.
+    rows: Vec<u32>,
+    columns: Vec<u32>,
+}
+
+impl CrtRenderer {
+    pub fn new(crt: Crt) -> Self {
+        CrtRenderer {
+            crt,
+            size: (0, 0, 0),
+            map: Vec::new(),
+            rows: Vec::new(),
+            columns: Vec::new(),
+        }
+    }
+
+    pub fn crt(&self) -> Crt {
+        self.crt
+    }
+
+    pub fn set_crt(&mut self, crt: Crt) {
+        self.crt = crt;
+        self.size = (0, 0, 0);
+    }
+
+    /// Draws colors, a width x height frame row by row, into out, which
+    /// holds 3 bytes for each of the width * scale x height * scale pixels.
+    /// Only what is brighter than background blooms.
+    pub fn render(
+        &mut self,
+        colors: &[Rgb],
+        background: Rgb,
+        width: usize,
+        height: usize,
+        scale: usize,
+        out: &mut [u8],
+    ) {
+        if self.size != (width, height, scale) {
+            self.resize(width, height, scale);
+        }
+        let glow = self.bloom(colors, background, width, height);
+        for (pixel, &source) in out.chunks_exact_mut(3).zip(&self.map) {
+            match source {
+                Some((x, y)) => {
+                    let color = glow[y / scale * width + x / scale];
+                    let brightness = (self.rows[y % scale] * self.columns[x % scale]) >> 8;
+                    for (out, &channel) in pixel.iter_mut().zip(&color) {
+                        *out = ((channel.min(255) * brightness) >> 8) as u8;
+                    }
+                }
+                None => pixel.copy_from_slice(&[0, 0, 0]),
+            }
+        }
+    }
+
+    fn resize(&mut self, width: usize, height: usize, scale: usize) {
+        self.size = (width, height, scale);
+        let (out_width, out_height) = (width * scale, height * scale);
+
+        // Barrel distortion: points are pushed outwards further the further
+        // they are from the centre, so the edges of the frame curve out of
+        // view.
+        let k = self.crt.curvature as f32 / 100.0 * 0.25;
+        self.map = (0..out_width * out_height)
+            .map(|index| {
+                let (x, y) = (index % out_width, index / out_width);
+                let u = (x as f32 + 0.5) / out_width as f32 * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / out_height as f32 * 2.0 - 1.0;
+                let (u, v) = (u * (1.0 + k * v * v), v * (1.0 + k * u * u));
+                if u.abs() >= 1.0 || v.abs() >= 1.0 {
+                    return None;
+                }
+                let x = ((u + 1.0) / 2.0 * out_width as f32) as usize;
+                let y = ((v + 1.0) / 2.0 * out_height as f32) as usize;
+                Some((x.min(out_width - 1), y.min(out_height - 1)))
+            })
+            .collect();
+
+        // The last third of each pixel's rows and columns is the gap.
+        let gap = (scale / 3).max(1);
+        let shade = |strength: u8, index: usize| {
+            if scale > 1 && index >= scale - gap {
+                256 - strength as u32 * 256 / 100
+            } else {
+                256
+            }
+        };
+        self.rows = (0..scale).map(|y| shade(self.crt.scanlines, y)).collect();
+        self.columns = (0..scale).map(|x| shade(self.crt.grid, x)).collect();
+    }
+
+    // Each pixel's colour plus the bloom from its eight neighbours, unclamped.
+    // A neighbour only glows by as much as it is brighter than the
+    // background, so empty screen stays the background colour.
+    fn bloom(&self, colors: &[Rgb], background: Rgb, width: usize, height: usize) -> Vec<[u32; 3]> {
+        let widen = |color: &Rgb| [color[0] as u32, color[1] as u32, color[2] as u32];
+        if self.crt.bloom == 0 {
+            return colors.iter().map(widen).collect();
+        }
+        let strength = self.crt.bloom as u32;
+        (0..width * height)
+            .map(|index| {
+                let (x, y) = (index % width, index / width);
+                let mut glow = [0; 3];
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        if (nx, ny) != (x, y) {
+                            let neighbour = colors[ny * width + nx];
+                            for ((glow, channel), bg) in
+                                glow.iter_mut().zip(&neighbour).zip(&background)
+                            {
+                                *glow += channel.saturating_sub(*bg) as u32;
+                            }
+                        }
+                    }
+                }
+                let mut color = widen(&colors[index]);
+                for (channel, glow) in color.iter_mut().zip(&glow) {
+                    *channel += glow * strength / (8 * 100);
+                }
+                color
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+#[path = "./crt_test.rs"]
+mod crt_test;
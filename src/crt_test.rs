@@ -0,0 +1,100 @@
+use super::*;
+
+const WHITE: Rgb = [255, 255, 255];
+const BLACK: Rgb = [0, 0, 0];
+
+// Renders a width x height frame at scale and returns the output pixels.
+fn render(crt: Crt, colors: &[Rgb], width: usize, height: usize, scale: usize) -> Vec<Rgb> {
+    let mut out = vec![0; width * scale * height * scale * 3];
+    CrtRenderer::new(crt).render(colors, BLACK, width, height, scale, &mut out);
+    out.chunks_exact(3)
+        .map(|rgb| [rgb[0], rgb[1], rgb[2]])
+        .collect()
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(Crt::parse("off"), Some(Crt::default()));
+    assert_eq!(Crt::parse("on"), Some(Crt::CLASSIC));
+    assert_eq!(
+        Crt::parse("scanlines=40, bloom=30"),
+        Some(Crt {
+            scanlines: 40,
+            bloom: 30,
+            ..Crt::default()
+        })
+    );
+    assert_eq!(Crt::parse("grid=101"), None);
+    assert_eq!(Crt::parse("blur=10"), None);
+    assert_eq!(Crt::parse("scanlines"), None);
+    assert!(Crt::default().is_off());
+    assert!(!Crt::CLASSIC.is_off());
+}
+
+#[test]
+fn test_off_scales_up() {
+    let out = render(Crt::default(), &[WHITE, BLACK], 2, 1, 3);
+    for (index, &color) in out.iter().enumerate() {
+        let x = index % 6;
+        assert_eq!(color, if x < 3 { WHITE } else { BLACK });
+    }
+}
+
+#[test]
+fn test_scanlines_and_grid() {
+    let crt = Crt {
+        scanlines: 50,
+        grid: 100,
+        ..Crt::default()
+    };
+    let out = render(crt, &[WHITE], 1, 1, 3);
+    assert_eq!(out[0], WHITE);
+    // The bottom row is half as bright, the right column black.
+    assert_eq!(out[6], [127, 127, 127]);
+    assert_eq!(out[2], BLACK);
+    assert_eq!(out[8], BLACK);
+}
+
+#[test]
+fn test_bloom_lights_neighbours() {
+    let crt = Crt {
+        bloom: 80,
+        ..Crt::default()
+    };
+    let out = render(crt, &[WHITE, BLACK, BLACK], 3, 1, 1);
+    assert_eq!(out[0], WHITE);
+    assert_eq!(out[1], [25, 25, 25]);
+    assert_eq!(out[2], BLACK);
+}
+
+#[test]
+fn test_bloom_ignores_the_background() {
+    let crt = Crt {
+        bloom: 100,
+        ..Crt::default()
+    };
+    let grey = [100, 100, 100];
+    let mut out = vec![0; 3 * 3];
+    CrtRenderer::new(crt).render(&[[200, 100, 100], grey, grey], grey, 3, 1, 1, &mut out);
+    assert_eq!(&out[3..], &[112, 100, 100, 100, 100, 100]);
+}
+
+#[test]
+fn test_scaled() {
+    let crt = Crt::CLASSIC.scaled(50);
+    assert_eq!((crt.scanlines, crt.bloom), (20, 15));
+    assert_eq!(Crt::CLASSIC.scaled(400).scanlines, 100);
+    assert!(Crt::CLASSIC.scaled(0).is_off());
+}
+
+#[test]
+fn test_curvature_darkens_corners() {
+    let crt = Crt {
+        curvature: 100,
+        ..Crt::default()
+    };
+    let out = render(crt, &[WHITE; 16 * 8], 16, 8, 2);
+    assert_eq!(out[0], BLACK);
+    assert_eq!(out[out.len() - 1], BLACK);
+    assert_eq!(out[8 * 32 + 16], WHITE);
+}
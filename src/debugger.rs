@@ -0,0 +1,427 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use clock::Clock;
+use error::EmulatorError;
+use instruction::Instruction;
+use processor::Processor;
+
+/// Something a watchpoint keeps an eye on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    /// RAM addresses start..end.
+    Memory(usize, usize),
+    /// Register Vx.
+    V(usize),
+    /// The index register.
+    I,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Break(usize),
+    Delete(usize),
+    Watch(Watch),
+    Unwatch(usize),
+    List,
+    Step,
+    Next,
+    Continue,
+    Pause,
+    Registers,
+    Stack,
+    Memory(usize, usize),
+    Help,
+}
+
+/// Why execution stopped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Breakpoint(usize),
+    Watchpoint(Watch, Vec<u8>, Vec<u8>),
+    Step,
+    Fault(EmulatorError),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Paused,
+    Running,
+    Step,
+    // Run until the CALL at pc returns to return_pc with the stack back at depth sp.
+    StepOver { return_pc: usize, sp: usize },
+}
+
+const HELP: &str = "\
+break <addr>          stop before executing addr
+delete <addr>         remove a breakpoint
+watch <addr>[..<end>] stop when RAM changes
+watch v<x> | watch i  stop when a register changes
+unwatch <n>           remove watchpoint n
+list                  show breakpoints and watchpoints
+step                  execute one instruction
+next                  like step, but runs CALLs to completion
+continue              resume execution
+pause                 stop execution
+regs                  show registers and timers
+stack                 show the call stack
+mem <addr> [len]      dump memory
+Addresses are hexadecimal.";
+
+/// Breakpoints, watchpoints and stepping around `Processor::tick`.
+///
+/// The debugger runs the processor a frame at a time like
+/// `Processor::run_frame`, but checks breakpoints before and watchpoints after
+/// every instruction. A frame interrupted by a stop is finished when
+/// execution resumes, so timers keep the same pace as they would undebugged.
+pub struct Debugger {
+    breakpoints: BTreeSet<usize>,
+    watchpoints: Vec<Watch>,
+    mode: Mode,
+    // Cycles left in a frame interrupted by a stop.
+    remaining: Option<u32>,
+    // Lets execution resume from the breakpoint it stopped at.
+    skip_breakpoint: Option<usize>,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debugger {
+    /// A debugger that starts paused.
+    pub fn new() -> Self {
+        Debugger {
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            mode: Mode::Paused,
+            remaining: None,
+            skip_breakpoint: None,
+        }
+    }
+
+    pub fn paused(&self) -> bool {
+        self.mode == Mode::Paused
+    }
+
+    pub fn pause(&mut self) {
+        self.mode = Mode::Paused;
+    }
+
+    /// Carries out a command and returns the text to show the user.
+    pub fn execute(&mut self, command: Command, processor: &Processor) -> String {
+        match command {
+            Command::Break(addr) => {
+                self.breakpoints.insert(addr);
+                format!("breakpoint at {:03X}", addr)
+            }
+            Command::Delete(addr) => {
+                if self.breakpoints.remove(&addr) {
+                    format!("deleted breakpoint at {:03X}", addr)
+                } else {
+                    format!("no breakpoint at {:03X}", addr)
+                }
+            }
+            Command::Watch(watch) => {
+                self.watchpoints.push(watch);
+                format!(
+                    "watchpoint {}: {}",
+                    self.watchpoints.len() - 1,
+                    describe(watch)
+                )
+            }
+            Command::Unwatch(index) => {
+                if index < self.watchpoints.len() {
+                    let watch = self.watchpoints.remove(index);
+                    format!("deleted watchpoint {}: {}", index, describe(watch))
+                } else {
+                    format!("no watchpoint {}", index)
+                }
+            }
+            Command::List => self.list(),
+            Command::Step => self.resume(Mode::Step, processor),
+            Command::Next => {
+                let call = processor
+                    .opcode_at(processor.pc())
+                    .ok()
+                    .and_then(Instruction::decode);
+                let mode = match call {
+                    Some(Instruction::Call(_)) => Mode::StepOver {
+                        return_pc: processor.pc() + 2,
+                        sp: processor.sp(),
+                    },
+                    _ => Mode::Step,
+                };
+                self.resume(mode, processor)
+            }
+            Command::Continue => self.resume(Mode::Running, processor),
+            Command::Pause => {
+                self.pause();
+                format!("paused at {:03X}", processor.pc())
+            }
+            Command::Registers => registers(processor),
+            Command::Stack => stack(processor),
+            Command::Memory(addr, len) => memory(processor, addr, len),
+            Command::Help => HELP.to_string(),
+        }
+    }
+
+    /// Runs the rest of the current frame unless paused. Returns whether
+    /// anything was drawn and, if execution stopped, why.
+    pub fn run_frame(
+        &mut self,
+        processor: &mut Processor,
+        keypad: [bool; 16],
+        clock: &mut Clock,
+    ) -> (bool, Option<StopReason>) {
+        let mut vram_changed = false;
+        if self.paused() {
+            return (vram_changed, None);
+        }
+
+        let mut remaining = match self.remaining.take() {
+            Some(remaining) => remaining,
+            None => clock.next_frame(),
+        };
+        while remaining > 0 {
+            let pc = processor.pc();
+            if self.breakpoints.contains(&pc) && self.skip_breakpoint != Some(pc) {
+                return self.stop(remaining, vram_changed, StopReason::Breakpoint(pc));
+            }
+            self.skip_breakpoint = None;
+
+            let before: Vec<Vec<u8>> = self
+                .watchpoints
+                .iter()
+                .map(|&watch| watched(processor, watch))
+                .collect();
+            match processor.tick(keypad) {
+                Ok(output) => vram_changed |= output.vram_changed,
+                Err(error) => return self.stop(remaining, vram_changed, StopReason::Fault(error)),
+            }
+            remaining -= 1;
+
+            for (&watch, old) in self.watchpoints.iter().zip(before) {
+                let new = watched(processor, watch);
+                if new != old {
+                    let reason = StopReason::Watchpoint(watch, old, new);
+                    return self.stop(remaining, vram_changed, reason);
+                }
+            }
+            match self.mode {
+                Mode::Step => return self.stop(remaining, vram_changed, StopReason::Step),
+                Mode::StepOver { return_pc, sp }
+                    if processor.pc() == return_pc && processor.sp() == sp =>
+                {
+                    return self.stop(remaining, vram_changed, StopReason::Step);
+                }
+                _ => {}
+            }
+        }
+        processor.tick_timers();
+        (vram_changed, None)
+    }
+
+    fn resume(&mut self, mode: Mode, processor: &Processor) -> String {
+        self.mode = mode;
+        self.skip_breakpoint = Some(processor.pc());
+        match mode {
+            Mode::Running => "continuing".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn stop(
+        &mut self,
+        remaining: u32,
+        vram_changed: bool,
+        reason: StopReason,
+    ) -> (bool, Option<StopReason>) {
+        self.mode = Mode::Paused;
+        self.remaining = Some(remaining);
+        (vram_changed, Some(reason))
+    }
+
+    fn list(&self) -> String {
+        let mut out = String::new();
+        for addr in &self.breakpoints {
+            let _ = writeln!(out, "breakpoint at {:03X}", addr);
+        }
+        for (index, &watch) in self.watchpoints.iter().enumerate() {
+            let _ = writeln!(out, "watchpoint {}: {}", index, describe(watch));
+        }
+        if out.is_empty() {
+            out.push_str("no breakpoints or watchpoints");
+        }
+        out.trim_end().to_string()
+    }
+}
+
+/// Whether an attached debugger is holding emulated time still. With none
+/// attached, it never is.
+pub fn halted(debugger: Option<&Debugger>) -> bool {
+    debugger.is_some_and(Debugger::paused)
+}
+
+/// Describes a stop, including the instruction execution stopped at.
+pub fn describe_stop(reason: &StopReason, processor: &Processor) -> String {
+    let location = match processor.opcode_at(processor.pc()) {
+        Ok(opcode) => match Instruction::decode(opcode) {
+            Some(instruction) => format!("{:03X}: {:04X}  {}", processor.pc(), opcode, instruction),
+            None => format!("{:03X}: {:04X}", processor.pc(), opcode),
+        },
+        Err(_) => format!("{:03X}", processor.pc()),
+    };
+    match *reason {
+        StopReason::Breakpoint(_) => format!("breakpoint at {}", location),
+        StopReason::Watchpoint(watch, ref old, ref new) => format!(
+            "{} changed from {} to {} at {}",
+            describe(watch),
+            hex(old),
+            hex(new),
+            location
+        ),
+        StopReason::Step => location,
+        StopReason::Fault(ref error) => format!("fault: {}", error),
+    }
+}
+
+/// Parses one line of REPL input.
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let command = match words.as_slice() {
+        ["b", addr] | ["break", addr] => Command::Break(parse_number(addr)?),
+        ["d", addr] | ["delete", addr] => Command::Delete(parse_number(addr)?),
+        ["w", target] | ["watch", target] => Command::Watch(parse_watch(target)?),
+        ["unwatch", index] => Command::Unwatch(
+            index
+                .parse()
+                .map_err(|_| format!("invalid watchpoint number: {}", index))?,
+        ),
+        ["l"] | ["list"] => Command::List,
+        ["s"] | ["step"] => Command::Step,
+        ["n"] | ["next"] => Command::Next,
+        ["c"] | ["continue"] => Command::Continue,
+        ["p"] | ["pause"] => Command::Pause,
+        ["r"] | ["regs"] => Command::Registers,
+        ["stack"] => Command::Stack,
+        ["x", addr] | ["mem", addr] => Command::Memory(parse_number(addr)?, 64),
+        ["x", addr, len] | ["mem", addr, len] => {
+            Command::Memory(parse_number(addr)?, parse_number(len)?)
+        }
+        ["h"] | ["help"] => Command::Help,
+        _ => return Err(format!("unknown command: {} (try help)", line.trim())),
+    };
+    Ok(command)
+}
+
+fn parse_number(text: &str) -> Result<usize, String> {
+    let digits = text.trim_start_matches("0x").trim_start_matches("0X");
+    usize::from_str_radix(digits, 16).map_err(|_| format!("invalid address: {}", text))
+}
+
+fn parse_watch(text: &str) -> Result<Watch, String> {
+    let lower = text.to_ascii_lowercase();
+    if lower == "i" {
+        return Ok(Watch::I);
+    }
+    if let Some(register) = lower.strip_prefix('v') {
+        return match usize::from_str_radix(register, 16) {
+            Ok(x) if x < 16 => Ok(Watch::V(x)),
+            _ => Err(format!("invalid register: {}", text)),
+        };
+    }
+    match lower.split_once("..") {
+        Some((start, end)) => {
+            let (start, end) = (parse_number(start)?, parse_number(end)?);
+            if start < end {
+                Ok(Watch::Memory(start, end))
+            } else {
+                Err(format!("empty range: {}", text))
+            }
+        }
+        None => {
+            let addr = parse_number(text)?;
+            Ok(Watch::Memory(addr, addr.saturating_add(1)))
+        }
+    }
+}
+
+fn watched(processor: &Processor, watch: Watch) -> Vec<u8> {
+    match watch {
+        Watch::Memory(start, end) => {
+            let ram = processor.ram();
+            ram[start.min(ram.len())..end.min(ram.len())].to_vec()
+        }
+        Watch::V(x) => vec![processor.v()[x]],
+        Watch::I => (processor.i() as u32).to_be_bytes().to_vec(),
+    }
+}
+
+fn describe(watch: Watch) -> String {
+    match watch {
+        Watch::Memory(start, end) if end == start.saturating_add(1) => format!("[{:03X}]", start),
+        Watch::Memory(start, end) => format!("[{:03X}..{:03X}]", start, end),
+        Watch::V(x) => format!("V{:X}", x),
+        Watch::I => "I".to_string(),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02X}", byte)).collect()
+}
+
+/// Formats PC, I, SP, the timers and V0..VF the way the `regs` command shows
+/// them.
+pub fn registers(processor: &Processor) -> String {
+    let mut out = format!(
+        "PC={:03X} I={:03X} SP={:X} DT={:02X} ST={:02X}\n",
+        processor.pc(),
+        processor.i(),
+        processor.sp(),
+        processor.delay_timer(),
+        processor.sound_timer()
+    );
+    for (x, value) in processor.v().iter().enumerate() {
+        let separator = if x % 8 == 7 { "\n" } else { " " };
+        let _ = write!(out, "V{:X}={:02X}{}", x, value, separator);
+    }
+    if let Some(x) = processor.waiting_for_key() {
+        let _ = write!(out, "waiting for a key into V{:X}", x);
+    }
+    out.trim_end().to_string()
+}
+
+fn stack(processor: &Processor) -> String {
+    if processor.stack().is_empty() {
+        return "stack is empty".to_string();
+    }
+    let mut out = String::new();
+    for (depth, addr) in processor.stack().iter().enumerate().rev() {
+        let _ = writeln!(out, "#{} {:03X}", depth, addr);
+    }
+    out.trim_end().to_string()
+}
+
+fn memory(processor: &Processor, addr: usize, len: usize) -> String {
+    let ram = processor.ram();
+    let end = addr.saturating_add(len).min(ram.len());
+    let mut out = String::new();
+    for row in (addr..end).step_by(16) {
+        let bytes: Vec<String> = ram[row..(row + 16).min(end)]
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        let _ = writeln!(out, "{:04X}: {}", row, bytes.join(" "));
+    }
+    if out.is_empty() {
+        out.push_str("address out of range");
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+#[path = "./debugger_test.rs"]
+mod debugger_test;
@@ -0,0 +1,224 @@
+use super::*;
+use error::ErrorKind;
+use sound::{Beeper, SoundOptions};
+use Quirks;
+
+// 200: LD V0, 1
+// 202: CALL 20A
+// 204: LD V1, 2
+// 206: JP 206
+// 208: (unused)
+// 20A: ADD V0, 1
+// 20C: LD [I], V0
+// 20E: RET
+const PROGRAM: [u8; 16] = [
+    0x60, 0x01, 0x22, 0x0A, 0x61, 0x02, 0x12, 0x06, 0x00, 0x00, 0x70, 0x01, 0xF0, 0x55, 0x00, 0xEE,
+];
+
+fn build() -> (Processor, Debugger, Clock) {
+    let mut processor = Processor::new(Quirks::SUPER_CHIP);
+    processor.load(&PROGRAM);
+    (processor, Debugger::new(), Clock::new(600))
+}
+
+fn run(
+    debugger: &mut Debugger,
+    processor: &mut Processor,
+    clock: &mut Clock,
+) -> Option<StopReason> {
+    for _ in 0..10 {
+        if let (_, Some(reason)) = debugger.run_frame(processor, [false; 16], clock) {
+            return Some(reason);
+        }
+    }
+    None
+}
+
+#[test]
+fn test_parse_command() {
+    assert_eq!(parse_command("break 20a"), Ok(Command::Break(0x20A)));
+    assert_eq!(parse_command("b 0x300"), Ok(Command::Break(0x300)));
+    assert_eq!(
+        parse_command("watch 300..310"),
+        Ok(Command::Watch(Watch::Memory(0x300, 0x310)))
+    );
+    assert_eq!(
+        parse_command("w 300"),
+        Ok(Command::Watch(Watch::Memory(0x300, 0x301)))
+    );
+    assert_eq!(parse_command("watch vF"), Ok(Command::Watch(Watch::V(15))));
+    assert_eq!(parse_command("watch I"), Ok(Command::Watch(Watch::I)));
+    assert_eq!(
+        parse_command("mem 200 10"),
+        Ok(Command::Memory(0x200, 0x10))
+    );
+    assert_eq!(parse_command("  next "), Ok(Command::Next));
+    assert!(parse_command("watch v10").is_err());
+    assert!(parse_command("watch 310..300").is_err());
+    assert!(parse_command("break").is_err());
+    assert!(parse_command("frobnicate").is_err());
+}
+
+#[test]
+fn test_starts_paused() {
+    let (mut processor, mut debugger, mut clock) = build();
+    assert_eq!(run(&mut debugger, &mut processor, &mut clock), None);
+    assert_eq!(processor.pc(), 0x200);
+    assert_eq!(clock.cycles(), 0);
+}
+
+#[test]
+fn test_breakpoint_and_continue() {
+    let (mut processor, mut debugger, mut clock) = build();
+    debugger.execute(Command::Break(0x20C), &processor);
+    debugger.execute(Command::Continue, &processor);
+    assert_eq!(
+        run(&mut debugger, &mut processor, &mut clock),
+        Some(StopReason::Breakpoint(0x20C))
+    );
+    assert_eq!(processor.v()[0], 2);
+    assert!(debugger.paused());
+
+    // Resuming steps off the breakpoint instead of stopping on it again.
+    debugger.execute(Command::Step, &processor);
+    assert_eq!(
+        run(&mut debugger, &mut processor, &mut clock),
+        Some(StopReason::Step)
+    );
+    assert_eq!(processor.pc(), 0x20E);
+}
+
+#[test]
+fn test_step_over_call() {
+    let (mut processor, mut debugger, mut clock) = build();
+    debugger.execute(Command::Step, &processor);
+    run(&mut debugger, &mut processor, &mut clock);
+    assert_eq!(processor.pc(), 0x202);
+
+    debugger.execute(Command::Next, &processor);
+    assert_eq!(
+        run(&mut debugger, &mut processor, &mut clock),
+        Some(StopReason::Step)
+    );
+    assert_eq!(processor.pc(), 0x204);
+    assert_eq!(processor.v()[0], 2);
+    assert_eq!(processor.sp(), 0);
+}
+
+#[test]
+fn test_watchpoints() {
+    let (mut processor, mut debugger, mut clock) = build();
+    debugger.execute(Command::Watch(Watch::Memory(0, 1)), &processor);
+    debugger.execute(Command::Watch(Watch::V(1)), &processor);
+    debugger.execute(Command::Continue, &processor);
+    assert_eq!(
+        run(&mut debugger, &mut processor, &mut clock),
+        Some(StopReason::Watchpoint(
+            Watch::Memory(0, 1),
+            vec![0xF0],
+            vec![0x02]
+        ))
+    );
+    assert_eq!(processor.pc(), 0x20E);
+
+    debugger.execute(Command::Continue, &processor);
+    assert_eq!(
+        run(&mut debugger, &mut processor, &mut clock),
+        Some(StopReason::Watchpoint(Watch::V(1), vec![0], vec![2]))
+    );
+    assert_eq!(processor.pc(), 0x206);
+}
+
+#[test]
+fn test_fault_pauses() {
+    let (mut processor, mut debugger, mut clock) = build();
+    processor.load(&[0x00, 0xEE]);
+    debugger.execute(Command::Continue, &processor);
+    match run(&mut debugger, &mut processor, &mut clock) {
+        Some(StopReason::Fault(error)) => assert_eq!(error.kind, ErrorKind::StackUnderflow),
+        other => panic!("expected a fault, got {:?}", other),
+    }
+    assert!(debugger.paused());
+    assert_eq!(processor.pc(), 0x200);
+}
+
+#[test]
+fn test_interrupted_frame_keeps_timer_pace() {
+    let (mut processor, mut debugger, mut clock) = build();
+    debugger.execute(Command::Break(0x206), &processor);
+    debugger.execute(Command::Continue, &processor);
+    run(&mut debugger, &mut processor, &mut clock);
+    assert_eq!(clock.frames(), 1);
+
+    debugger.execute(Command::Delete(0x206), &processor);
+    debugger.execute(Command::Continue, &processor);
+    debugger.run_frame(&mut processor, [false; 16], &mut clock);
+    assert_eq!(clock.frames(), 1);
+    debugger.run_frame(&mut processor, [false; 16], &mut clock);
+    assert_eq!(clock.frames(), 2);
+}
+
+#[test]
+fn test_dumps() {
+    let (processor, mut debugger, _) = build();
+    let registers = debugger.execute(Command::Registers, &processor);
+    assert!(registers.starts_with("PC=200 I=000 SP=0"));
+    assert_eq!(
+        debugger.execute(Command::Memory(0x200, 4), &processor),
+        "0200: 60 01 22 0A"
+    );
+    assert_eq!(
+        debugger.execute(Command::Stack, &processor),
+        "stack is empty"
+    );
+    assert_eq!(
+        debugger.execute(Command::List, &processor),
+        "no breakpoints or watchpoints"
+    );
+}
+
+#[test]
+fn test_huge_addresses() {
+    let (processor, mut debugger, _) = build();
+    let max = format!("{:x}", usize::MAX);
+    assert_eq!(
+        parse_command(&format!("watch {}", max)),
+        Ok(Command::Watch(Watch::Memory(usize::MAX, usize::MAX)))
+    );
+    assert_eq!(
+        debugger.execute(Command::Memory(usize::MAX, usize::MAX), &processor),
+        "address out of range"
+    );
+    assert_eq!(
+        debugger.execute(Command::Memory(0xFFFC, usize::MAX), &processor),
+        "FFFC: 00 00 00 00"
+    );
+    debugger.execute(
+        Command::Watch(Watch::Memory(usize::MAX, usize::MAX)),
+        &processor,
+    );
+    assert!(debugger
+        .execute(Command::List, &processor)
+        .contains("[FFFFFFFFFFFFFFFF]"));
+}
+
+// The window only plays a frame's sound while no debugger holds time still; a
+// debugger that was never attached mustn't count as paused.
+#[test]
+fn test_sound_plays_without_a_debugger() {
+    assert!(!halted(None));
+    assert!(halted(Some(&Debugger::new())));
+
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&[0x60, 0x3C, 0xF0, 0x18, 0x12, 0x04]);
+    let mut clock = Clock::new(600);
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    let mut beeper = Beeper::new(SoundOptions::default(), 8000);
+    beeper.set_latency(0.0);
+    if !halted(None) {
+        beeper.push(&processor.take_sound(), 1.0 / 60.0);
+    }
+    let mut out = [0.0; 133];
+    beeper.fill(&mut out);
+    assert!(out.iter().any(|&sample| sample != 0.0));
+}
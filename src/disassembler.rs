@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use instruction::Instruction;
+
+const DATA_PER_LINE: usize = 8;
+
+/// Disassembles a ROM or memory range loaded at `origin`, following control
+/// flow from `origin`.
+pub fn disassemble(data: &[u8], origin: usize) -> String {
+    disassemble_from(data, origin, &[origin])
+}
+
+/// Disassembles `data` loaded at `origin`, following control flow from each
+/// entry point. Jump and call targets get labels, and bytes that are never
+/// reached as code are listed as `db` data.
+pub fn disassemble_from(data: &[u8], origin: usize, entry_points: &[usize]) -> String {
+    let code = trace(data, origin, entry_points);
+    let labels = labels(data, origin, &code);
+    let address = |addr: usize| match labels.get(&addr) {
+        Some(label) => label.clone(),
+        None => format!("#{:03X}", addr),
+    };
+
+    let mut out = String::new();
+    let end = origin + data.len();
+    let mut addr = origin;
+    while addr < end {
+        if let Some(label) = labels.get(&addr) {
+            writeln!(out, "{}:", label).unwrap();
+        }
+        let (text, raw, size) = match code.instructions.get(&addr) {
+            Some(&instruction) => {
+                let opcode = word_at(data, origin, addr).unwrap();
+                let mut text = String::new();
+                instruction.write_mnemonic(&mut text, &address).unwrap();
+                let mut raw = format!("{:04X}", opcode);
+                if instruction == Instruction::LoadLongI {
+                    let long = word_at(data, origin, addr + 2).unwrap();
+                    write!(text, " #{:04X}", long).unwrap();
+                    write!(raw, " {:04X}", long).unwrap();
+                }
+                (text, raw, instruction.size())
+            }
+            None => {
+                // A data run stops at the next label or instruction.
+                let mut size = 1;
+                while size < DATA_PER_LINE
+                    && addr + size < end
+                    && !code.covered[addr + size - origin]
+                    && !labels.contains_key(&(addr + size))
+                {
+                    size += 1;
+                }
+                let bytes: Vec<String> = data[addr - origin..addr - origin + size]
+                    .iter()
+                    .map(|byte| format!("#{:02X}", byte))
+                    .collect();
+                (format!("db {}", bytes.join(", ")), String::new(), size)
+            }
+        };
+        writeln!(out, "    {:<24}; {:04X}  {}", text, addr, raw).unwrap();
+        addr += size;
+    }
+    out.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct Code {
+    // Decoded instructions by address.
+    instructions: BTreeMap<usize, Instruction>,
+    // Which bytes of data belong to an instruction.
+    covered: Vec<bool>,
+    jumps: BTreeSet<usize>,
+    calls: BTreeSet<usize>,
+}
+
+fn word_at(data: &[u8], origin: usize, addr: usize) -> Option<u16> {
+    if addr < origin || addr + 2 > origin + data.len() {
+        return None;
+    }
+    let offset = addr - origin;
+    Some((data[offset] as u16) << 8 | data[offset + 1] as u16)
+}
+
+// Recursive descent: follow every path from the entry points until it jumps
+// somewhere already seen, returns, or runs into something that doesn't
+// decode.
+fn trace(data: &[u8], origin: usize, entry_points: &[usize]) -> Code {
+    let mut code = Code {
+        instructions: BTreeMap::new(),
+        covered: vec![false; data.len()],
+        jumps: BTreeSet::new(),
+        calls: BTreeSet::new(),
+    };
+    let mut pending = entry_points.to_vec();
+    while let Some(mut addr) = pending.pop() {
+        while !code.instructions.contains_key(&addr) {
+            let instruction = match word_at(data, origin, addr).and_then(Instruction::decode) {
+                Some(instruction) => instruction,
+                None => break,
+            };
+            let next = addr + instruction.size();
+            if next > origin + data.len()
+                || code.covered[addr - origin..next - origin].contains(&true)
+            {
+                break;
+            }
+            for covered in &mut code.covered[addr - origin..next - origin] {
+                *covered = true;
+            }
+            code.instructions.insert(addr, instruction);
+
+            match instruction {
+                Instruction::Jump(target) => {
+                    code.jumps.insert(target);
+                    pending.push(target);
+                    break;
+                }
+                Instruction::Call(target) => {
+                    code.calls.insert(target);
+                    pending.push(target);
+                }
+                Instruction::Ret | Instruction::Exit | Instruction::JumpOffset(_) => break,
+                Instruction::SkipEqByte(..)
+                | Instruction::SkipNeByte(..)
+                | Instruction::SkipEq(..)
+                | Instruction::SkipNe(..)
+                | Instruction::SkipKey(_)
+                | Instruction::SkipNoKey(_) => {
+                    // Skipping F000 NNNN steps over both of its words.
+                    let skipped = match word_at(data, origin, next) {
+                        Some(0xF000) => 4,
+                        _ => 2,
+                    };
+                    pending.push(next + skipped);
+                }
+                _ => {}
+            }
+            addr = next;
+        }
+    }
+    code
+}
+
+// Labels targets that start a line of the listing: an instruction, or a byte
+// that ended up as data.
+fn labels(data: &[u8], origin: usize, code: &Code) -> BTreeMap<usize, String> {
+    let starts_line = |addr: usize| {
+        addr >= origin
+            && addr < origin + data.len()
+            && (code.instructions.contains_key(&addr) || !code.covered[addr - origin])
+    };
+    let mut labels = BTreeMap::new();
+    for &addr in code.jumps.iter().filter(|&&addr| starts_line(addr)) {
+        labels.insert(addr, format!("label_{:03X}", addr));
+    }
+    for &addr in code.calls.iter().filter(|&&addr| starts_line(addr)) {
+        labels.insert(addr, format!("sub_{:03X}", addr));
+    }
+    labels
+}
+
+#[cfg(test)]
+#[path = "./disassembler_test.rs"]
+mod disassembler_test;
@@ -0,0 +1,65 @@
+use super::*;
+
+#[test]
+fn test_mnemonics() {
+    let decode = |opcode| Instruction::decode(opcode).unwrap().to_string();
+    assert_eq!(decode(0xD125), "DRW V1, V2, 5");
+    assert_eq!(decode(0x8AB6), "SHR VA, VB");
+    assert_eq!(decode(0xB300), "JP V0, #300");
+    assert_eq!(decode(0x5132), "SAVE V1 - V3");
+    assert_eq!(decode(0xF329), "LD F, V3");
+    assert_eq!(decode(0xF265), "LD V2, [I]");
+    assert_eq!(decode(0x0123), "SYS #123");
+    assert_eq!(Instruction::decode(0x5121), None);
+    assert_eq!(Instruction::decode(0xFFFF), None);
+}
+
+#[test]
+fn test_labels_and_data() {
+    // The routine at 20A is only reachable through the CALL, and the word at
+    // 208 is never executed.
+    let rom = [
+        0x60, 0x01, 0x22, 0x0A, 0x61, 0x02, 0x12, 0x06, 0x00, 0x00, 0x70, 0x01, 0xF0, 0x55, 0x00,
+        0xEE,
+    ];
+    assert_eq!(
+        disassemble(&rom, 0x200),
+        "    LD V0, #01              ; 0200  6001
+    CALL sub_20A            ; 0202  220A
+    LD V1, #02              ; 0204  6102
+label_206:
+    JP label_206            ; 0206  1206
+    db #00, #00             ; 0208
+sub_20A:
+    ADD V0, #01             ; 020A  7001
+    LD [I], V0              ; 020C  F055
+    RET                     ; 020E  00EE"
+    );
+}
+
+#[test]
+fn test_skip_over_long_load() {
+    // SE V0, 0 can skip the four-byte F000 NNNN, so tracing continues at 206,
+    // and nothing after EXIT is code.
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x03, 0x00, 0x00, 0xFD, 0x12, 0x34];
+    assert_eq!(
+        disassemble(&rom, 0x200),
+        "    SE V0, #00              ; 0200  3000
+    LD I, long #0300        ; 0202  F000 0300
+    EXIT                    ; 0206  00FD
+    db #12, #34             ; 0208"
+    );
+}
+
+#[test]
+fn test_memory_range() {
+    // A range that doesn't start at 0x200, entered in the middle.
+    let memory = [0xA2, 0x00, 0x00, 0xE0, 0x13, 0x02];
+    assert_eq!(
+        disassemble_from(&memory, 0x300, &[0x302]),
+        "    db #A2, #00             ; 0300
+label_302:
+    CLS                     ; 0302  00E0
+    JP label_302            ; 0304  1302"
+    );
+}
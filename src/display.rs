@@ -0,0 +1,238 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use config::{self, ConfigError};
+use crt::Crt;
+use flicker::AntiFlicker;
+use snapshot::PALETTE;
+
+pub type Rgb = [u8; 3];
+
+/// Colours indexed by the two XO-CHIP plane bits: background, plane 1,
+/// plane 2 and both planes. Plain CHIP-8 only uses the first two.
+pub type Palette = [Rgb; 4];
+
+/// Built-in palettes for `--palette`. The Octo ones match the themes of the
+/// Octo IDE, so XO-CHIP games look the way their authors saw them.
+pub const PALETTES: [(&str, Palette); 9] = [
+    ("mono", PALETTE),
+    (
+        "green",
+        [
+            [0x0A, 0x14, 0x0A],
+            [0x33, 0xFF, 0x66],
+            [0x1E, 0x99, 0x3D],
+            [0x99, 0xFF, 0xB3],
+        ],
+    ),
+    (
+        "amber",
+        [
+            [0x14, 0x0C, 0x00],
+            [0xFF, 0xB0, 0x00],
+            [0x99, 0x69, 0x00],
+            [0xFF, 0xDB, 0x80],
+        ],
+    ),
+    (
+        "octo",
+        [
+            [0x99, 0x66, 0x00],
+            [0xFF, 0xCC, 0x00],
+            [0xFF, 0x66, 0x00],
+            [0x66, 0x22, 0x00],
+        ],
+    ),
+    (
+        "octo-lcd",
+        [
+            [0xF9, 0xFF, 0xB3],
+            [0x3D, 0x80, 0x26],
+            [0xAB, 0xCC, 0x47],
+            [0x00, 0x13, 0x1A],
+        ],
+    ),
+    (
+        "octo-hotdog",
+        [
+            [0x00, 0x00, 0x00],
+            [0xFF, 0x00, 0x00],
+            [0xFF, 0xFF, 0x00],
+            [0xFF, 0xFF, 0xFF],
+        ],
+    ),
+    (
+        "octo-gray",
+        [
+            [0xAA, 0xAA, 0xAA],
+            [0x00, 0x00, 0x00],
+            [0xFF, 0xFF, 0xFF],
+            [0x66, 0x66, 0x66],
+        ],
+    ),
+    (
+        "octo-cga0",
+        [
+            [0x00, 0x00, 0x00],
+            [0x00, 0xFF, 0x00],
+            [0xFF, 0x00, 0x00],
+            [0xFF, 0xFF, 0x00],
+        ],
+    ),
+    (
+        "octo-cga1",
+        [
+            [0x00, 0x00, 0x00],
+            [0xFF, 0x00, 0xFF],
+            [0x00, 0xFF, 0xFF],
+            [0xFF, 0xFF, 0xFF],
+        ],
+    ),
+];
+
+pub const DEFAULT_SCALE: u32 = 20;
+
+pub fn palette(name: &str) -> Option<Palette> {
+    PALETTES
+        .iter()
+        .find(|&&(palette_name, _)| palette_name.eq_ignore_ascii_case(name))
+        .map(|&(_, palette)| palette)
+}
+
+/// Parses `#RRGGBB`, with or without the `#`.
+pub fn parse_color(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    Some([(value >> 16) as u8, (value >> 8) as u8, value as u8])
+}
+
+/// How the window looks, from `display.toml` and the command line:
+///
+/// ```text
+/// palette = "amber"
+/// foreground = "#FFC040"
+/// background = "#000000"
+/// scale = 10
+/// fullscreen = false
+/// anti_flicker = "phosphor:4"
+/// vsync = true
+/// crt = "scanlines=40,bloom=30"
+/// ```
+///
+/// The foreground and background replace the palette's first two colours.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayOptions {
+    pub palette: Palette,
+    /// Window pixels per CHIP-8 pixel, before any resizing.
+    pub scale: u32,
+    pub fullscreen: bool,
+    pub anti_flicker: AntiFlicker,
+    /// Present at most once per 60 Hz vblank, synced to the monitor, rather
+    /// than after every emulated frame that drew something.
+    pub vsync: bool,
+    /// Software CRT effects, off unless set.
+    pub crt: Crt,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            palette: PALETTE,
+            scale: DEFAULT_SCALE,
+            fullscreen: false,
+            anti_flicker: AntiFlicker::Off,
+            vsync: false,
+            crt: Crt::default(),
+        }
+    }
+}
+
+impl DisplayOptions {
+    /// `display.toml` in `config::config_dir`.
+    pub fn default_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join("display.toml"))
+    }
+
+    /// Reads a config file; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<DisplayOptions, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(text) => DisplayOptions::parse(&text),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(DisplayOptions::default())
+            }
+            Err(error) => Err(ConfigError::Io(error)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<DisplayOptions, ConfigError> {
+        let mut options = DisplayOptions::default();
+        let mut foreground = None;
+        let mut background = None;
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |message: &str| ConfigError::Invalid(index + 1, message.to_string());
+            let line = config::strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected <setting> = <value>"))?;
+            let value = value.trim();
+            let flag = || match value {
+                "true" => Ok(true),
+                "false" => Ok(false),
+                _ => Err(invalid("expected true or false")),
+            };
+            let color = || {
+                config::parse_string(value)
+                    .and_then(|color| parse_color(&color))
+                    .ok_or_else(|| invalid("expected a colour like \"#FFB000\""))
+            };
+            match key.trim() {
+                "palette" => {
+                    options.palette = config::parse_string(value)
+                        .and_then(|name| palette(&name))
+                        .ok_or_else(|| invalid("unknown palette"))?
+                }
+                "foreground" => foreground = Some(color()?),
+                "background" => background = Some(color()?),
+                "scale" => {
+                    options.scale = value
+                        .parse()
+                        .ok()
+                        .filter(|&scale| scale > 0)
+                        .ok_or_else(|| invalid("scale is a whole number above 0"))?
+                }
+                "fullscreen" => options.fullscreen = flag()?,
+                "anti_flicker" => {
+                    options.anti_flicker = config::parse_string(value)
+                        .and_then(|mode| AntiFlicker::parse(&mode))
+                        .ok_or_else(|| {
+                            invalid("expected \"off\", \"phosphor\", \"or\" or \"average\"")
+                        })?
+                }
+                "vsync" => options.vsync = flag()?,
+                "crt" => {
+                    options.crt = config::parse_string(value)
+                        .and_then(|crt| Crt::parse(&crt))
+                        .ok_or_else(|| {
+                            invalid("expected \"off\", \"on\" or effects like \"scanlines=40\"")
+                        })?
+                }
+                _ => return Err(invalid("unknown setting")),
+            }
+        }
+        // Colours win over the palette, wherever they are in the file.
+        options.palette[1] = foreground.unwrap_or(options.palette[1]);
+        options.palette[0] = background.unwrap_or(options.palette[0]);
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+#[path = "./display_test.rs"]
+mod display_test;
@@ -0,0 +1,66 @@
+use super::*;
+
+#[test]
+fn test_parse_color() {
+    assert_eq!(parse_color("#FFB000"), Some([0xFF, 0xB0, 0x00]));
+    assert_eq!(parse_color("0a140a"), Some([0x0A, 0x14, 0x0A]));
+    assert_eq!(parse_color("#FFF"), None);
+    assert_eq!(parse_color("#GGGGGG"), None);
+}
+
+#[test]
+fn test_palette_names() {
+    assert_eq!(palette("mono"), Some(PALETTE));
+    assert_eq!(palette("Amber").unwrap()[1], [0xFF, 0xB0, 0x00]);
+    assert_eq!(palette("sepia"), None);
+}
+
+#[test]
+fn test_parse() {
+    let options = DisplayOptions::parse(
+        "# my settings\n\
+         foreground = \"#FFC040\"\n\
+         palette = \"octo\"  # XO-CHIP colours\n\
+         scale = 10\n\
+         fullscreen = true\n\
+         anti_flicker = \"or:3\"\n\
+         vsync = true\n\
+         crt = \"grid=20,curvature=10\"\n",
+    )
+    .unwrap();
+    let octo = palette("octo").unwrap();
+    assert_eq!(options.palette[0], octo[0]);
+    assert_eq!(options.palette[1], [0xFF, 0xC0, 0x40]);
+    assert_eq!(options.palette[2..], octo[2..]);
+    assert_eq!(options.scale, 10);
+    assert!(options.fullscreen);
+    assert_eq!(options.anti_flicker, AntiFlicker::Or(3));
+    assert!(options.vsync);
+    assert_eq!(
+        options.crt,
+        Crt {
+            grid: 20,
+            curvature: 10,
+            ..Crt::default()
+        }
+    );
+    assert_eq!(
+        DisplayOptions::parse("").unwrap(),
+        DisplayOptions::default()
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |text| match DisplayOptions::parse(text) {
+        Err(ConfigError::Invalid(line, _)) => line,
+        other => panic!("expected an error, got {:?}", other),
+    };
+    assert_eq!(error("palette = \"sepia\""), 1);
+    assert_eq!(error("\nscale = 0"), 2);
+    assert_eq!(error("fullscreen = yes"), 1);
+    assert_eq!(error("foreground = #FFFFFF"), 1);
+    assert_eq!(error("volume = 3"), 1);
+    assert_eq!(error("anti_flicker = \"blur\""), 1);
+    assert_eq!(error("crt = \"scanlines=200\""), 1);
+}
@@ -0,0 +1,53 @@
+use std::error::Error;
+use std::fmt;
+
+/// What went wrong while executing an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// CALL with all 16 stack entries in use.
+    StackOverflow,
+    /// RET with an empty stack.
+    StackUnderflow,
+    /// A fetch, load or store touched an address past the end of RAM.
+    MemoryOutOfRange(usize),
+    /// SKP/SKNP on a register holding a value that isn't a key (0-F).
+    InvalidKey(u8),
+    /// The opcode doesn't decode to any supported instruction.
+    UnknownOpcode,
+}
+
+/// A fault raised by `Processor::tick`, with the instruction that caused it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmulatorError {
+    pub kind: ErrorKind,
+    /// Address of the faulting instruction.
+    pub pc: usize,
+    /// The faulting instruction, or 0 if it couldn't be fetched.
+    pub opcode: u16,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorKind::StackOverflow => write!(f, "stack overflow"),
+            ErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            ErrorKind::MemoryOutOfRange(addr) => {
+                write!(f, "memory access out of range at {:#06x}", addr)
+            }
+            ErrorKind::InvalidKey(key) => write!(f, "invalid key index {:#04x}", key),
+            ErrorKind::UnknownOpcode => write!(f, "unknown opcode"),
+        }
+    }
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (pc {:#06x}, opcode {:04X})",
+            self.kind, self.pc, self.opcode
+        )
+    }
+}
+
+impl Error for EmulatorError {}
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use display::{Palette, Rgb};
+use Vram;
+
+/// Ways of hiding the flicker of sprites that are erased and redrawn with
+/// XOR every frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AntiFlicker {
+    /// Show every frame as it is.
+    #[default]
+    Off,
+    /// Pixels fade out over this many frames after going dark, like a CRT's
+    /// phosphor.
+    Phosphor(u8),
+    /// A pixel shows lit if it was lit in any of the last this many frames.
+    Or(u8),
+    /// A pixel shows the average of its colours over the last this many
+    /// frames.
+    Average(u8),
+}
+
+impl AntiFlicker {
+    /// Parses `off`, or `phosphor`, `or` or `average` with an optional frame
+    /// count: `phosphor:6`.
+    pub fn parse(text: &str) -> Option<AntiFlicker> {
+        let (name, frames) = match text.split_once(':') {
+            Some((name, frames)) => (name, Some(frames.parse().ok().filter(|&n| n > 0)?)),
+            None => (text, None),
+        };
+        match name {
+            "off" if frames.is_none() => Some(AntiFlicker::Off),
+            "phosphor" => Some(AntiFlicker::Phosphor(frames.unwrap_or(4))),
+            "or" => Some(AntiFlicker::Or(frames.unwrap_or(2))),
+            "average" => Some(AntiFlicker::Average(frames.unwrap_or(2))),
+            _ => None,
+        }
+    }
+
+    // Frames of history the mode looks at.
+    fn history(self) -> usize {
+        match self {
+            AntiFlicker::Or(frames) | AntiFlicker::Average(frames) => frames as usize,
+            _ => 1,
+        }
+    }
+}
+
+/// Turns the frames the emulator produces into the colours to show, applying
+/// an `AntiFlicker` mode. Feed it every frame, changed or not, so the fading
+/// and blending keep time with the emulation.
+pub struct FrameFilter {
+    mode: AntiFlicker,
+    size: (usize, usize),
+    // Recent frames as palette indices, row by row, newest last.
+    frames: VecDeque<Vec<u8>>,
+    // For phosphor: frames since each pixel was last lit, and its colour then.
+    ages: Vec<u8>,
+    lit: Vec<u8>,
+}
+
+impl FrameFilter {
+    pub fn new(mode: AntiFlicker) -> Self {
+        FrameFilter {
+            mode,
+            size: (0, 0),
+            frames: VecDeque::new(),
+            ages: Vec::new(),
+            lit: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> AntiFlicker {
+        self.mode
+    }
+
+    /// Adds the top-left width x height corner of vram as the newest frame.
+    /// A change of resolution starts over.
+    pub fn push(&mut self, vram: &Vram, width: usize, height: usize) {
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.frames.clear();
+            self.ages = vec![u8::MAX; width * height];
+            self.lit = vec![0; width * height];
+        }
+        let frame: Vec<u8> = vram
+            .iter()
+            .take(height)
+            .flat_map(|row| row[..width].iter().map(|&pixel| pixel & 0x3))
+            .collect();
+
+        if let AntiFlicker::Phosphor(_) = self.mode {
+            for ((age, lit), &pixel) in self.ages.iter_mut().zip(self.lit.iter_mut()).zip(&frame) {
+                if pixel != 0 {
+                    *age = 0;
+                    *lit = pixel;
+                } else {
+                    *age = age.saturating_add(1);
+                }
+            }
+        }
+
+        if self.frames.len() == self.mode.history() {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// The colours to show, row by row, for the frames pushed so far.
+    pub fn colors(&self, palette: &Palette) -> Vec<Rgb> {
+        let newest = match self.frames.back() {
+            Some(newest) => newest,
+            None => return Vec::new(),
+        };
+        match self.mode {
+            AntiFlicker::Off => newest
+                .iter()
+                .map(|&pixel| palette[pixel as usize])
+                .collect(),
+            AntiFlicker::Phosphor(frames) => newest
+                .iter()
+                .zip(self.ages.iter().zip(&self.lit))
+                .map(|(&pixel, (&age, &lit))| {
+                    if pixel != 0 || age >= frames {
+                        palette[pixel as usize]
+                    } else {
+                        let fade = age as u32 * 256 / frames as u32;
+                        mix(&[(palette[lit as usize], 256 - fade), (palette[0], fade)])
+                    }
+                })
+                .collect(),
+            AntiFlicker::Or(_) => (0..newest.len())
+                .map(|index| {
+                    let pixel = self
+                        .frames
+                        .iter()
+                        .fold(0, |pixel, frame| pixel | frame[index]);
+                    palette[pixel as usize]
+                })
+                .collect(),
+            AntiFlicker::Average(_) => (0..newest.len())
+                .map(|index| {
+                    let colors: Vec<(Rgb, u32)> = self
+                        .frames
+                        .iter()
+                        .map(|frame| (palette[frame[index] as usize], 1))
+                        .collect();
+                    mix(&colors)
+                })
+                .collect(),
+        }
+    }
+}
+
+// Weighted average of colours.
+fn mix(colors: &[(Rgb, u32)]) -> Rgb {
+    let total: u32 = colors.iter().map(|&(_, weight)| weight).sum();
+    let mut mixed = [0; 3];
+    for (channel, value) in mixed.iter_mut().enumerate() {
+        let sum: u32 = colors
+            .iter()
+            .map(|&(color, weight)| color[channel] as u32 * weight)
+            .sum();
+        *value = ((sum + total / 2) / total) as u8;
+    }
+    mixed
+}
+
+#[cfg(test)]
+#[path = "./flicker_test.rs"]
+mod flicker_test;
@@ -0,0 +1,79 @@
+use super::*;
+use snapshot::PALETTE;
+use {SCHIP_HEIGHT, SCHIP_WIDTH};
+
+const WHITE: Rgb = [255, 255, 255];
+const BLACK: Rgb = [0, 0, 0];
+
+// A 2x1 frame with the given pixels.
+fn frame(left: u8, right: u8) -> Vram {
+    let mut vram = [[0; SCHIP_WIDTH]; SCHIP_HEIGHT];
+    vram[0][0] = left;
+    vram[0][1] = right;
+    vram
+}
+
+fn colors(filter: &mut FrameFilter, frames: &[(u8, u8)]) -> Vec<Rgb> {
+    for &(left, right) in frames {
+        filter.push(&frame(left, right), 2, 1);
+    }
+    filter.colors(&PALETTE)
+}
+
+#[test]
+fn test_parse() {
+    assert_eq!(AntiFlicker::parse("off"), Some(AntiFlicker::Off));
+    assert_eq!(
+        AntiFlicker::parse("phosphor"),
+        Some(AntiFlicker::Phosphor(4))
+    );
+    assert_eq!(AntiFlicker::parse("or:3"), Some(AntiFlicker::Or(3)));
+    assert_eq!(
+        AntiFlicker::parse("average:2"),
+        Some(AntiFlicker::Average(2))
+    );
+    assert_eq!(AntiFlicker::parse("or:0"), None);
+    assert_eq!(AntiFlicker::parse("off:2"), None);
+    assert_eq!(AntiFlicker::parse("blur"), None);
+}
+
+#[test]
+fn test_off() {
+    let mut filter = FrameFilter::new(AntiFlicker::Off);
+    assert_eq!(colors(&mut filter, &[(1, 0), (0, 1)]), [BLACK, WHITE]);
+}
+
+#[test]
+fn test_phosphor() {
+    let mut filter = FrameFilter::new(AntiFlicker::Phosphor(4));
+    assert_eq!(colors(&mut filter, &[(1, 0)]), [WHITE, BLACK]);
+    assert_eq!(colors(&mut filter, &[(0, 0)])[0], [191, 191, 191]);
+    assert_eq!(colors(&mut filter, &[(0, 0)])[0], [128, 128, 128]);
+    assert_eq!(colors(&mut filter, &[(0, 0), (0, 0)])[0], BLACK);
+    assert_eq!(colors(&mut filter, &[(1, 0)])[0], WHITE);
+}
+
+#[test]
+fn test_or() {
+    let mut filter = FrameFilter::new(AntiFlicker::Or(2));
+    assert_eq!(colors(&mut filter, &[(1, 0), (0, 1)]), [WHITE, WHITE]);
+    assert_eq!(colors(&mut filter, &[(0, 0)]), [BLACK, WHITE]);
+    assert_eq!(colors(&mut filter, &[(0, 2), (0, 1)])[1], PALETTE[3]);
+}
+
+#[test]
+fn test_average() {
+    let mut filter = FrameFilter::new(AntiFlicker::Average(2));
+    assert_eq!(
+        colors(&mut filter, &[(1, 1), (0, 1)]),
+        [[128, 128, 128], WHITE]
+    );
+}
+
+#[test]
+fn test_resolution_change() {
+    let mut filter = FrameFilter::new(AntiFlicker::Or(2));
+    colors(&mut filter, &[(1, 1)]);
+    filter.push(&frame(0, 0), 4, 2);
+    assert_eq!(filter.colors(&PALETTE), [BLACK; 8]);
+}
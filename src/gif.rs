@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::io::prelude::*;
+
+use display::Palette;
+use snapshot;
+use Vram;
+
+// GIF delays are in hundredths of a second, and browsers slow down anything
+// shorter than two, so faster changes are dropped.
+const MIN_DELAY: u64 = 2;
+const FRAMES_PER_SECOND: u64 = 60;
+
+// LZW codes are at most 12 bits.
+const MAX_CODES: u16 = 4096;
+// Four colours need two bits, the least GIF allows.
+const MIN_CODE_SIZE: u8 = 2;
+
+/// Writes an animated GIF, looping forever, of 4-colour frames.
+pub struct GifWriter<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+}
+
+impl<W: Write> GifWriter<W> {
+    pub fn new(mut out: W, width: u16, height: u16, palette: &Palette) -> io::Result<Self> {
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // A global colour table of 2^(1 + 1) colours.
+        out.write_all(&[0x91, 0, 0])?;
+        out.write_all(&palette.concat())?;
+        // The NETSCAPE2.0 extension: loop forever.
+        out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(GifWriter { out, width, height })
+    }
+
+    /// Adds a frame of palette indices, row by row, shown for delay
+    /// hundredths of a second.
+    pub fn frame(&mut self, indices: &[u8], delay: u16) -> io::Result<()> {
+        // Graphic control extension, for the delay.
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x00])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0x00, 0x00])?;
+        // Image descriptor: the whole screen, no local colour table.
+        self.out.write_all(&[0x2C, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0x00, MIN_CODE_SIZE])?;
+        for block in lzw(indices).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0x00])
+    }
+
+    /// Writes the trailer and hands back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3B])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// Packs codes into bytes, least significant bit first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u8) {
+        self.bits |= (code as u32) << self.count;
+        self.count += size;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+// GIF's variable-width LZW compression.
+fn lzw(indices: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut out = BitWriter {
+        bytes: Vec::new(),
+        bits: 0,
+        count: 0,
+    };
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = end + 1;
+    let mut size = MIN_CODE_SIZE + 1;
+    out.write(clear, size);
+
+    let mut pixels = indices.iter();
+    let mut prefix = match pixels.next() {
+        Some(&pixel) => pixel as u16,
+        None => {
+            out.write(end, size);
+            return out.finish();
+        }
+    };
+    for &pixel in pixels {
+        if let Some(&code) = codes.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        out.write(prefix, size);
+        if next == MAX_CODES {
+            // The table is full: start a new one.
+            out.write(clear, size);
+            codes.clear();
+            next = end + 1;
+            size = MIN_CODE_SIZE + 1;
+        } else {
+            codes.insert((prefix, pixel), next);
+            // The decoder builds its table a code behind, so it widens
+            // once this code, rather than the last that fits, is in.
+            if next == 1 << size {
+                size += 1;
+            }
+            next += 1;
+        }
+        prefix = pixel as u16;
+    }
+    out.write(prefix, size);
+    out.write(end, size);
+    out.finish()
+}
+
+/// Records 60 Hz frames into a GIF. Runs of identical frames become one
+/// frame with a longer delay, and frames of a different resolution are
+/// stretched to the size the recording started at.
+pub struct GifRecorder<W: Write> {
+    writer: GifWriter<W>,
+    width: usize,
+    height: usize,
+    // The frame waiting for its delay to be known.
+    pending: Option<Vec<u8>>,
+    frames: u64,
+    // Hundredths of a second written so far.
+    written: u64,
+}
+
+impl<W: Write> GifRecorder<W> {
+    /// Starts a recording width x height pixels in size. GIF sizes are
+    /// 16-bit, so anything larger fails with `InvalidInput`.
+    pub fn new(out: W, width: usize, height: usize, palette: &Palette) -> io::Result<Self> {
+        let (gif_width, gif_height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(gif_width), Ok(gif_height)) => (gif_width, gif_height),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{}x{} is too large for a GIF", width, height),
+                ))
+            }
+        };
+        Ok(GifRecorder {
+            writer: GifWriter::new(out, gif_width, gif_height, palette)?,
+            width,
+            height,
+            pending: None,
+            frames: 0,
+            written: 0,
+        })
+    }
+
+    /// Adds the top-left width x height corner of vram as the next frame.
+    pub fn push(&mut self, vram: &Vram, width: usize, height: usize) -> io::Result<()> {
+        let frame = snapshot::indices(vram, width, height, self.width, self.height);
+        if self.pending.as_ref() != Some(&frame) {
+            self.flush(false)?;
+            self.pending = Some(frame);
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Writes the last frame and the trailer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.flush(true)?;
+        self.writer.finish()
+    }
+
+    // Writes the pending frame if it lasted long enough, or always at the end.
+    fn flush(&mut self, last: bool) -> io::Result<()> {
+        let end = (self.frames * 100 + FRAMES_PER_SECOND / 2) / FRAMES_PER_SECOND;
+        let delay = end.saturating_sub(self.written);
+        if delay < MIN_DELAY && !last {
+            return Ok(());
+        }
+        if let Some(frame) = self.pending.take() {
+            self.writer
+                .frame(&frame, delay.clamp(MIN_DELAY, u16::MAX as u64) as u16)?;
+            self.written = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[path = "./gif_test.rs"]
+mod gif_test;
@@ -0,0 +1,164 @@
+use super::*;
+use snapshot::PALETTE;
+use {CHIP8_HEIGHT, CHIP8_WIDTH, SCHIP_HEIGHT, SCHIP_WIDTH};
+
+// Reads codes the way `BitWriter` packs them.
+fn read_code(data: &[u8], position: &mut usize, size: u8) -> u16 {
+    let mut code = 0;
+    for bit in 0..size as usize {
+        let index = *position + bit;
+        code |= ((data[index / 8] >> (index % 8) & 1) as u16) << bit;
+    }
+    *position += size as usize;
+    code
+}
+
+// A plain GIF LZW decoder, to check the encoder against.
+fn unlzw(data: &[u8]) -> Vec<u8> {
+    let clear = 1u16 << MIN_CODE_SIZE;
+    let end = clear + 1;
+    let mut table: Vec<Vec<u8>> = Vec::new();
+    let mut size = MIN_CODE_SIZE + 1;
+    let mut previous: Option<Vec<u8>> = None;
+    let mut out = Vec::new();
+    let mut position = 0;
+    loop {
+        let code = read_code(data, &mut position, size);
+        if code == clear {
+            table = (0..clear).map(|index| vec![index as u8]).collect();
+            table.push(Vec::new());
+            table.push(Vec::new());
+            size = MIN_CODE_SIZE + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            return out;
+        }
+        let entry = match previous {
+            Some(ref previous) => {
+                let entry = if (code as usize) < table.len() {
+                    table[code as usize].clone()
+                } else {
+                    let mut entry = previous.clone();
+                    entry.push(previous[0]);
+                    entry
+                };
+                if table.len() < MAX_CODES as usize {
+                    let mut added = previous.clone();
+                    added.push(entry[0]);
+                    table.push(added);
+                    if table.len() == 1 << size && size < 12 {
+                        size += 1;
+                    }
+                }
+                entry
+            }
+            None => table[code as usize].clone(),
+        };
+        out.extend_from_slice(&entry);
+        previous = Some(entry);
+    }
+}
+
+#[test]
+fn test_lzw_round_trip() {
+    assert_eq!(unlzw(&lzw(&[])), []);
+    assert_eq!(unlzw(&lzw(&[3])), [3]);
+    let runs = [0u8; 1000];
+    assert_eq!(unlzw(&lzw(&runs)), runs.to_vec());
+    // Enough varied data to fill the code table and clear it several times.
+    let noisy: Vec<u8> = (0..50_000u32)
+        .map(|index| (index.wrapping_mul(2_654_435_761) >> 13) as u8 & 0x3)
+        .collect();
+    assert_eq!(unlzw(&lzw(&noisy)), noisy);
+}
+
+// The image data of every frame in a GIF written by `GifWriter`, with its
+// delay.
+fn frames(gif: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    // Header, screen descriptor, colour table and loop extension.
+    let mut position = 6 + 7 + 12 + 19;
+    let mut frames = Vec::new();
+    while gif[position] == 0x21 {
+        let delay = u16::from_le_bytes([gif[position + 4], gif[position + 5]]);
+        position += 8 + 10 + 1;
+        let mut data = Vec::new();
+        while gif[position] != 0 {
+            let length = gif[position] as usize;
+            data.extend_from_slice(&gif[position + 1..position + 1 + length]);
+            position += 1 + length;
+        }
+        position += 1;
+        frames.push((delay, unlzw(&data)));
+    }
+    assert_eq!(&gif[position..], &[0x3B]);
+    frames
+}
+
+#[test]
+fn test_writer() {
+    let mut writer = GifWriter::new(Vec::new(), 2, 2, &PALETTE).unwrap();
+    writer.frame(&[0, 1, 2, 3], 5).unwrap();
+    let gif = writer.finish().unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+    assert_eq!(&gif[6..10], &[2, 0, 2, 0]);
+    assert_eq!(&gif[13..25], &PALETTE.concat()[..]);
+    assert_eq!(frames(&gif), [(5, vec![0, 1, 2, 3])]);
+}
+
+#[test]
+fn test_recorder_rejects_oversized_gifs() {
+    let error = GifRecorder::new(Vec::new(), 128 * 600, 64, &PALETTE)
+        .err()
+        .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    assert!(GifRecorder::new(Vec::new(), 64, 65536, &PALETTE).is_err());
+    assert!(GifRecorder::new(Vec::new(), 65535, 1, &PALETTE).is_ok());
+}
+
+#[test]
+fn test_recorder_merges_repeated_frames() {
+    let blank = [[0; SCHIP_WIDTH]; SCHIP_HEIGHT];
+    let mut lit = blank;
+    lit[0][0] = 1;
+    let mut recorder = GifRecorder::new(Vec::new(), 2, 1, &PALETTE).unwrap();
+    // Half a second blank, a frame each lit and blank, then a second lit and
+    // a second blank. The lone blank frame rounds to a hundredth of a second,
+    // too short to keep.
+    for _ in 0..30 {
+        recorder.push(&blank, 2, 1).unwrap();
+    }
+    recorder.push(&lit, 2, 1).unwrap();
+    recorder.push(&blank, 2, 1).unwrap();
+    for _ in 0..60 {
+        recorder.push(&lit, 2, 1).unwrap();
+    }
+    for _ in 0..60 {
+        recorder.push(&blank, 2, 1).unwrap();
+    }
+    let gif = recorder.finish().unwrap();
+    assert_eq!(
+        frames(&gif),
+        [
+            (50, vec![0, 0]),
+            (2, vec![1, 0]),
+            (101, vec![1, 0]),
+            (100, vec![0, 0]),
+        ]
+    );
+}
+
+#[test]
+fn test_recorder_stretches_other_resolutions() {
+    let mut vram = [[0; SCHIP_WIDTH]; SCHIP_HEIGHT];
+    vram[0][0] = 1;
+    let mut recorder = GifRecorder::new(Vec::new(), SCHIP_WIDTH, SCHIP_HEIGHT, &PALETTE).unwrap();
+    recorder.push(&vram, CHIP8_WIDTH, CHIP8_HEIGHT).unwrap();
+    let gif = recorder.finish().unwrap();
+    let image = &frames(&gif)[0].1;
+    assert_eq!(image.len(), SCHIP_WIDTH * SCHIP_HEIGHT);
+    assert_eq!(&image[..3], &[1, 1, 0]);
+    assert_eq!(&image[SCHIP_WIDTH..SCHIP_WIDTH + 3], &[1, 1, 0]);
+    assert_eq!(image[SCHIP_WIDTH * 2], 0);
+}
@@ -0,0 +1,106 @@
+use clock::Clock;
+use error::EmulatorError;
+use processor::Processor;
+
+/// How long a headless run lasts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// Whole 60 Hz frames, timers included.
+    Frames(u64),
+    /// Instructions, which may end a run partway through a frame.
+    Cycles(u64),
+}
+
+/// Keypad input for a headless run, as the set of keys held from a given
+/// frame on.
+///
+/// A script is a list of `frame:keys` entries separated by spaces or commas,
+/// where keys are hex digits, or `-` for none. `"60:5 70:- 120:4a"` holds 5
+/// from frame 60 to 69, then nothing until 4 and A from frame 120 on.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    // Sorted by frame.
+    changes: Vec<(u64, [bool; 16])>,
+}
+
+impl KeyScript {
+    pub fn parse(script: &str) -> Result<KeyScript, String> {
+        let mut changes = Vec::new();
+        for entry in script.split(|c: char| c == ',' || c.is_whitespace()) {
+            if entry.is_empty() {
+                continue;
+            }
+            let (frame, keys) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("expected frame:keys, got {}", entry))?;
+            let frame: u64 = frame
+                .parse()
+                .map_err(|_| format!("invalid frame number: {}", frame))?;
+            let mut keypad = [false; 16];
+            if keys != "-" {
+                for key in keys.chars() {
+                    let key = key
+                        .to_digit(16)
+                        .ok_or_else(|| format!("invalid key: {}", key))?;
+                    keypad[key as usize] = true;
+                }
+            }
+            changes.push((frame, keypad));
+        }
+        changes.sort_by_key(|&(frame, _)| frame);
+        Ok(KeyScript { changes })
+    }
+
+    /// The keys held during frame, counting from 0.
+    pub fn keypad(&self, frame: u64) -> [bool; 16] {
+        self.changes
+            .iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or([false; 16], |&(_, keypad)| keypad)
+    }
+}
+
+/// Runs the processor without a display until the limit is reached or the
+/// program exits. Frames and cycles are counted from the start of this run.
+pub fn run(
+    processor: &mut Processor,
+    clock: &mut Clock,
+    limit: Limit,
+    keys: &KeyScript,
+) -> Result<(), EmulatorError> {
+    run_with(processor, clock, limit, keys, |_| {})
+}
+
+/// Like `run`, calling each_frame after every whole frame, timers included,
+/// to capture what it drew and played.
+pub fn run_with<F: FnMut(&mut Processor)>(
+    processor: &mut Processor,
+    clock: &mut Clock,
+    limit: Limit,
+    keys: &KeyScript,
+    mut each_frame: F,
+) -> Result<(), EmulatorError> {
+    let mut frames = 0;
+    let mut cycles = 0;
+    loop {
+        if limit == Limit::Frames(frames) || processor.exited() {
+            return Ok(());
+        }
+        let keypad = keys.keypad(frames);
+        for _ in 0..clock.next_frame() {
+            if limit == Limit::Cycles(cycles) {
+                return Ok(());
+            }
+            processor.tick(keypad)?;
+            cycles += 1;
+        }
+        processor.tick_timers();
+        each_frame(processor);
+        frames += 1;
+    }
+}
+
+#[cfg(test)]
+#[path = "./headless_test.rs"]
+mod headless_test;
@@ -0,0 +1,93 @@
+use super::*;
+use assembler::assemble;
+use Quirks;
+
+fn build_processor(source: &str) -> Processor {
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&assemble(source).unwrap());
+    processor
+}
+
+#[test]
+fn test_parse_key_script() {
+    let script = KeyScript::parse("120:4a, 60:5 70:-").unwrap();
+    assert_eq!(script.keypad(0), [false; 16]);
+    assert!(script.keypad(60)[5]);
+    assert!(script.keypad(69)[5]);
+    assert_eq!(script.keypad(70), [false; 16]);
+    let keypad = script.keypad(500);
+    assert!(keypad[4] && keypad[0xA] && !keypad[5]);
+
+    assert!(KeyScript::parse("60").is_err());
+    assert!(KeyScript::parse("x:1").is_err());
+    assert!(KeyScript::parse("1:g").is_err());
+}
+
+#[test]
+fn test_run_frames() {
+    let mut processor = build_processor("LD V0, 30\nLD DT, V0\nloop: JP loop");
+    let mut clock = Clock::new(600);
+    run(
+        &mut processor,
+        &mut clock,
+        Limit::Frames(10),
+        &KeyScript::default(),
+    )
+    .unwrap();
+    assert_eq!(clock.frames(), 10);
+    assert_eq!(processor.delay_timer(), 20);
+}
+
+#[test]
+fn test_run_cycles() {
+    let mut processor = build_processor("loop: ADD V0, 1\nJP loop");
+    let mut clock = Clock::new(600);
+    run(
+        &mut processor,
+        &mut clock,
+        Limit::Cycles(25),
+        &KeyScript::default(),
+    )
+    .unwrap();
+    assert_eq!(processor.v()[0], 13);
+}
+
+#[test]
+fn test_scripted_keys() {
+    let mut processor = build_processor("LD V1, K\nEXIT");
+    let mut clock = Clock::default();
+    let keys = KeyScript::parse("3:7").unwrap();
+    run(&mut processor, &mut clock, Limit::Frames(100), &keys).unwrap();
+    assert_eq!(processor.v()[1], 7);
+    assert!(processor.exited());
+    assert_eq!(clock.frames(), 4);
+}
+
+#[test]
+fn test_fault() {
+    let mut processor = build_processor("RET");
+    let mut clock = Clock::default();
+    let error = run(
+        &mut processor,
+        &mut clock,
+        Limit::Frames(1),
+        &KeyScript::default(),
+    );
+    assert!(error.is_err());
+}
+
+#[test]
+fn test_each_frame() {
+    let mut processor = build_processor("LD V0, 30\nLD DT, V0\nloop: JP loop");
+    let mut clock = Clock::new(600);
+    let mut timers = Vec::new();
+    run_with(
+        &mut processor,
+        &mut clock,
+        Limit::Frames(3),
+        &KeyScript::default(),
+        |processor| timers.push(processor.delay_timer()),
+    )
+    .unwrap();
+    assert_eq!(timers, [29, 28, 27]);
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+
+/// A decoded opcode. Both the interpreter and the disassembler decode through
+/// `Instruction::decode`, so they always agree on what a word means.
+///
+/// Register operands are indices into V, addresses and nibbles are widened to
+/// `usize`. `LoadLongI` is followed by a second word holding the address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN, a call into the host machine's own code, which is ignored.
+    Sys(usize),
+    ScrollDown(usize),
+    ScrollUp(usize),
+    Cls,
+    Ret,
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Low,
+    High,
+    Jump(usize),
+    Call(usize),
+    SkipEqByte(usize, u8),
+    SkipNeByte(usize, u8),
+    SkipEq(usize, usize),
+    SaveRange(usize, usize),
+    LoadRange(usize, usize),
+    LoadByte(usize, u8),
+    AddByte(usize, u8),
+    Move(usize, usize),
+    Or(usize, usize),
+    And(usize, usize),
+    Xor(usize, usize),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Shr(usize, usize),
+    Subn(usize, usize),
+    Shl(usize, usize),
+    SkipNe(usize, usize),
+    LoadI(usize),
+    JumpOffset(usize),
+    Random(usize, u8),
+    Draw(usize, usize, usize),
+    SkipKey(usize),
+    SkipNoKey(usize),
+    LoadLongI,
+    Plane(usize),
+    Audio,
+    GetDelay(usize),
+    WaitKey(usize),
+    SetDelay(usize),
+    SetSound(usize),
+    AddI(usize),
+    Font(usize),
+    BigFont(usize),
+    Bcd(usize),
+    Pitch(usize),
+    Store(usize),
+    Restore(usize),
+    SaveFlags(usize),
+    LoadFlags(usize),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let nibbles = (
+            (opcode & 0xF000) >> 12,
+            (opcode & 0x0F00) >> 8,
+            (opcode & 0x00F0) >> 4,
+            opcode & 0x000F,
+        );
+        let nnn = (opcode & 0x0FFF) as usize;
+        let kk = (opcode & 0x00FF) as u8;
+        let x = nibbles.1 as usize;
+        let y = nibbles.2 as usize;
+        let n = nibbles.3 as usize;
+
+        let instruction = match nibbles {
+            (0x00, 0x00, 0x0c, _) => Instruction::ScrollDown(n),
+            (0x00, 0x00, 0x0d, _) => Instruction::ScrollUp(n),
+            (0x00, 0x00, 0x0e, 0x00) => Instruction::Cls,
+            (0x00, 0x00, 0x0e, 0x0e) => Instruction::Ret,
+            (0x00, 0x00, 0x0f, 0x0b) => Instruction::ScrollRight,
+            (0x00, 0x00, 0x0f, 0x0c) => Instruction::ScrollLeft,
+            (0x00, 0x00, 0x0f, 0x0d) => Instruction::Exit,
+            (0x00, 0x00, 0x0f, 0x0e) => Instruction::Low,
+            (0x00, 0x00, 0x0f, 0x0f) => Instruction::High,
+            (0x00, _, _, _) => Instruction::Sys(nnn),
+            (0x01, _, _, _) => Instruction::Jump(nnn),
+            (0x02, _, _, _) => Instruction::Call(nnn),
+            (0x03, _, _, _) => Instruction::SkipEqByte(x, kk),
+            (0x04, _, _, _) => Instruction::SkipNeByte(x, kk),
+            (0x05, _, _, 0x00) => Instruction::SkipEq(x, y),
+            (0x05, _, _, 0x02) => Instruction::SaveRange(x, y),
+            (0x05, _, _, 0x03) => Instruction::LoadRange(x, y),
+            (0x06, _, _, _) => Instruction::LoadByte(x, kk),
+            (0x07, _, _, _) => Instruction::AddByte(x, kk),
+            (0x08, _, _, 0x00) => Instruction::Move(x, y),
+            (0x08, _, _, 0x01) => Instruction::Or(x, y),
+            (0x08, _, _, 0x02) => Instruction::And(x, y),
+            (0x08, _, _, 0x03) => Instruction::Xor(x, y),
+            (0x08, _, _, 0x04) => Instruction::Add(x, y),
+            (0x08, _, _, 0x05) => Instruction::Sub(x, y),
+            (0x08, _, _, 0x06) => Instruction::Shr(x, y),
+            (0x08, _, _, 0x07) => Instruction::Subn(x, y),
+            (0x08, _, _, 0x0e) => Instruction::Shl(x, y),
+            (0x09, _, _, 0x00) => Instruction::SkipNe(x, y),
+            (0x0a, _, _, _) => Instruction::LoadI(nnn),
+            (0x0b, _, _, _) => Instruction::JumpOffset(nnn),
+            (0x0c, _, _, _) => Instruction::Random(x, kk),
+            (0x0d, _, _, _) => Instruction::Draw(x, y, n),
+            (0x0e, _, 0x09, 0x0e) => Instruction::SkipKey(x),
+            (0x0e, _, 0x0a, 0x01) => Instruction::SkipNoKey(x),
+            (0x0f, 0x00, 0x00, 0x00) => Instruction::LoadLongI,
+            (0x0f, _, 0x00, 0x01) => Instruction::Plane(x),
+            (0x0f, 0x00, 0x00, 0x02) => Instruction::Audio,
+            (0x0f, _, 0x00, 0x07) => Instruction::GetDelay(x),
+            (0x0f, _, 0x00, 0x0a) => Instruction::WaitKey(x),
+            (0x0f, _, 0x01, 0x05) => Instruction::SetDelay(x),
+            (0x0f, _, 0x01, 0x08) => Instruction::SetSound(x),
+            (0x0f, _, 0x01, 0x0e) => Instruction::AddI(x),
+            (0x0f, _, 0x02, 0x09) => Instruction::Font(x),
+            (0x0f, _, 0x03, 0x00) => Instruction::BigFont(x),
+            (0x0f, _, 0x03, 0x03) => Instruction::Bcd(x),
+            (0x0f, _, 0x03, 0x0a) => Instruction::Pitch(x),
+            (0x0f, _, 0x05, 0x05) => Instruction::Store(x),
+            (0x0f, _, 0x06, 0x05) => Instruction::Restore(x),
+            (0x0f, _, 0x07, 0x05) => Instruction::SaveFlags(x),
+            (0x0f, _, 0x08, 0x05) => Instruction::LoadFlags(x),
+            _ => return None,
+        };
+        Some(instruction)
+    }
+
+    /// The inverse of `decode`. Operands are masked to their field widths.
+    pub fn encode(self) -> u16 {
+        let nnn = |base: u16, addr: usize| base | (addr as u16 & 0x0FFF);
+        let xkk = |base: u16, x: usize, kk: u8| base | (x as u16 & 0xF) << 8 | kk as u16;
+        let xyn = |base: u16, x: usize, y: usize, n: usize| {
+            base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | (n as u16 & 0xF)
+        };
+        match self {
+            Instruction::Sys(addr) => nnn(0x0000, addr),
+            Instruction::ScrollDown(n) => xyn(0x00C0, 0, 0, n),
+            Instruction::ScrollUp(n) => xyn(0x00D0, 0, 0, n),
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Low => 0x00FE,
+            Instruction::High => 0x00FF,
+            Instruction::Jump(addr) => nnn(0x1000, addr),
+            Instruction::Call(addr) => nnn(0x2000, addr),
+            Instruction::SkipEqByte(x, kk) => xkk(0x3000, x, kk),
+            Instruction::SkipNeByte(x, kk) => xkk(0x4000, x, kk),
+            Instruction::SkipEq(x, y) => xyn(0x5000, x, y, 0x0),
+            Instruction::SaveRange(x, y) => xyn(0x5000, x, y, 0x2),
+            Instruction::LoadRange(x, y) => xyn(0x5000, x, y, 0x3),
+            Instruction::LoadByte(x, kk) => xkk(0x6000, x, kk),
+            Instruction::AddByte(x, kk) => xkk(0x7000, x, kk),
+            Instruction::Move(x, y) => xyn(0x8000, x, y, 0x0),
+            Instruction::Or(x, y) => xyn(0x8000, x, y, 0x1),
+            Instruction::And(x, y) => xyn(0x8000, x, y, 0x2),
+            Instruction::Xor(x, y) => xyn(0x8000, x, y, 0x3),
+            Instruction::Add(x, y) => xyn(0x8000, x, y, 0x4),
+            Instruction::Sub(x, y) => xyn(0x8000, x, y, 0x5),
+            Instruction::Shr(x, y) => xyn(0x8000, x, y, 0x6),
+            Instruction::Subn(x, y) => xyn(0x8000, x, y, 0x7),
+            Instruction::Shl(x, y) => xyn(0x8000, x, y, 0xE),
+            Instruction::SkipNe(x, y) => xyn(0x9000, x, y, 0x0),
+            Instruction::LoadI(addr) => nnn(0xA000, addr),
+            Instruction::JumpOffset(addr) => nnn(0xB000, addr),
+            Instruction::Random(x, kk) => xkk(0xC000, x, kk),
+            Instruction::Draw(x, y, n) => xyn(0xD000, x, y, n),
+            Instruction::SkipKey(x) => xkk(0xE000, x, 0x9E),
+            Instruction::SkipNoKey(x) => xkk(0xE000, x, 0xA1),
+            Instruction::LoadLongI => 0xF000,
+            Instruction::Plane(n) => xkk(0xF000, n, 0x01),
+            Instruction::Audio => 0xF002,
+            Instruction::GetDelay(x) => xkk(0xF000, x, 0x07),
+            Instruction::WaitKey(x) => xkk(0xF000, x, 0x0A),
+            Instruction::SetDelay(x) => xkk(0xF000, x, 0x15),
+            Instruction::SetSound(x) => xkk(0xF000, x, 0x18),
+            Instruction::AddI(x) => xkk(0xF000, x, 0x1E),
+            Instruction::Font(x) => xkk(0xF000, x, 0x29),
+            Instruction::BigFont(x) => xkk(0xF000, x, 0x30),
+            Instruction::Bcd(x) => xkk(0xF000, x, 0x33),
+            Instruction::Pitch(x) => xkk(0xF000, x, 0x3A),
+            Instruction::Store(x) => xkk(0xF000, x, 0x55),
+            Instruction::Restore(x) => xkk(0xF000, x, 0x65),
+            Instruction::SaveFlags(x) => xkk(0xF000, x, 0x75),
+            Instruction::LoadFlags(x) => xkk(0xF000, x, 0x85),
+        }
+    }
+
+    /// Length in bytes, including the address word of `LoadLongI`.
+    pub fn size(self) -> usize {
+        match self {
+            Instruction::LoadLongI => 4,
+            _ => 2,
+        }
+    }
+
+    /// Writes the mnemonic, formatting code addresses with `address` so a
+    /// listing can substitute labels. `LoadLongI` is written without its
+    /// address word, which the caller appends.
+    pub fn write_mnemonic<W: fmt::Write>(
+        self,
+        out: &mut W,
+        address: &dyn Fn(usize) -> String,
+    ) -> fmt::Result {
+        match self {
+            Instruction::Sys(addr) => write!(out, "SYS #{:03X}", addr),
+            Instruction::ScrollDown(n) => write!(out, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(out, "SCU {}", n),
+            Instruction::Cls => write!(out, "CLS"),
+            Instruction::Ret => write!(out, "RET"),
+            Instruction::ScrollRight => write!(out, "SCR"),
+            Instruction::ScrollLeft => write!(out, "SCL"),
+            Instruction::Exit => write!(out, "EXIT"),
+            Instruction::Low => write!(out, "LOW"),
+            Instruction::High => write!(out, "HIGH"),
+            Instruction::Jump(addr) => write!(out, "JP {}", address(addr)),
+            Instruction::Call(addr) => write!(out, "CALL {}", address(addr)),
+            Instruction::SkipEqByte(x, kk) => write!(out, "SE V{:X}, #{:02X}", x, kk),
+            Instruction::SkipNeByte(x, kk) => write!(out, "SNE V{:X}, #{:02X}", x, kk),
+            Instruction::SkipEq(x, y) => write!(out, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(out, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(out, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadByte(x, kk) => write!(out, "LD V{:X}, #{:02X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(out, "ADD V{:X}, #{:02X}", x, kk),
+            Instruction::Move(x, y) => write!(out, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(out, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(out, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(out, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add(x, y) => write!(out, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(out, "SUB V{:X}, V{:X}", x, y),
+            Instruction::Shr(x, y) => write!(out, "SHR V{:X}, V{:X}", x, y),
+            Instruction::Subn(x, y) => write!(out, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::Shl(x, y) => write!(out, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNe(x, y) => write!(out, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(addr) => write!(out, "LD I, #{:03X}", addr),
+            Instruction::JumpOffset(addr) => write!(out, "JP V0, {}", address(addr)),
+            Instruction::Random(x, kk) => write!(out, "RND V{:X}, #{:02X}", x, kk),
+            Instruction::Draw(x, y, n) => write!(out, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey(x) => write!(out, "SKP V{:X}", x),
+            Instruction::SkipNoKey(x) => write!(out, "SKNP V{:X}", x),
+            Instruction::LoadLongI => write!(out, "LD I, long"),
+            Instruction::Plane(n) => write!(out, "PLANE {}", n),
+            Instruction::Audio => write!(out, "AUDIO"),
+            Instruction::GetDelay(x) => write!(out, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(out, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(out, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(out, "LD ST, V{:X}", x),
+            Instruction::AddI(x) => write!(out, "ADD I, V{:X}", x),
+            Instruction::Font(x) => write!(out, "LD F, V{:X}", x),
+            Instruction::BigFont(x) => write!(out, "LD HF, V{:X}", x),
+            Instruction::Bcd(x) => write!(out, "LD B, V{:X}", x),
+            Instruction::Pitch(x) => write!(out, "PITCH V{:X}", x),
+            Instruction::Store(x) => write!(out, "LD [I], V{:X}", x),
+            Instruction::Restore(x) => write!(out, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags(x) => write!(out, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(out, "LD V{:X}, R", x),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_mnemonic(f, &|addr| format!("#{:03X}", addr))
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use config::{self, ConfigError};
+
+/// Host keys for the hex keypad, indexed by keypad key. On the keyboard:
+///
+/// ```text
+/// 1 2 3 C      1 2 3 4
+/// 4 5 6 D  <-  Q W E R
+/// 7 8 9 E      A S D F
+/// A 0 B F      Z X C V
+/// ```
+///
+/// On a game controller the D-pad and left stick are 2, 4, 6 and 8, which
+/// most games use for movement, and the A, B, X and Y buttons are 5, 0, A
+/// and B.
+pub const DEFAULT_KEYS: [&[&str]; 16] = [
+    &["X", "pad:b"],
+    &["1"],
+    &["2", "pad:dpup", "pad:lefty-"],
+    &["3"],
+    &["Q", "pad:dpleft", "pad:leftx-"],
+    &["W", "pad:a"],
+    &["E", "pad:dpright", "pad:leftx+"],
+    &["A"],
+    &["S", "pad:dpdown", "pad:lefty+"],
+    &["D"],
+    &["Z", "pad:x"],
+    &["C", "pad:y"],
+    &["4"],
+    &["R"],
+    &["F"],
+    &["V"],
+];
+
+/// Controller bindings are host keys named with this prefix and an SDL
+/// button name (`pad:a`, `pad:dpup`, `pad:start`) or an axis name and
+/// direction (`pad:leftx-`, `pad:righty+`).
+pub const PAD_PREFIX: &str = "pad:";
+
+// Whether a host key name is a controller input.
+fn is_pad(name: &str) -> bool {
+    name.get(..PAD_PREFIX.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(PAD_PREFIX))
+}
+
+/// How far, in percent, a stick must be pushed to press its keypad key.
+pub const DEFAULT_STICK_THRESHOLD: u8 = 50;
+
+/// The host keys bound to each keypad key. Host keys are SDL key names
+/// ("Q", "Up", "Space", "Keypad 5") or controller inputs (see `PAD_PREFIX`),
+/// compared without regard to case.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyMap {
+    keys: [Vec<String>; 16],
+    stick_threshold: u8,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut keys: [Vec<String>; 16] = Default::default();
+        for (hosts, names) in keys.iter_mut().zip(DEFAULT_KEYS.iter()) {
+            hosts.extend(names.iter().map(|name| name.to_string()));
+        }
+        KeyMap {
+            keys,
+            stick_threshold: DEFAULT_STICK_THRESHOLD,
+        }
+    }
+}
+
+impl KeyMap {
+    /// The keypad key a host key is bound to, if any.
+    pub fn keypad_key(&self, host: &str) -> Option<usize> {
+        self.keys
+            .iter()
+            .position(|hosts| hosts.iter().any(|name| name.eq_ignore_ascii_case(host)))
+    }
+
+    /// The host keys bound to a keypad key.
+    pub fn host_keys(&self, keypad: usize) -> &[String] {
+        &self.keys[keypad]
+    }
+
+    /// Stick deflection, in percent, that counts as a press.
+    pub fn stick_threshold(&self) -> u8 {
+        self.stick_threshold
+    }
+
+    // Binds keypad to exactly hosts, taking them away from other keypad keys.
+    fn set(&mut self, keypad: usize, hosts: &[String]) {
+        for other in self.keys.iter_mut() {
+            other.retain(|name| !hosts.iter().any(|host| host.eq_ignore_ascii_case(name)));
+        }
+        self.keys[keypad] = hosts.to_vec();
+    }
+}
+
+// What one config section changes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Section {
+    // Keypad key -> host keys, for the keys the section mentions.
+    keys: BTreeMap<usize, Vec<String>>,
+    stick_threshold: Option<u8>,
+}
+
+/// Key bindings as stored in the config file: changes to the default layout
+/// for every ROM, and further changes for particular ROMs, identified by
+/// `rom_hash`.
+///
+/// The file is a small subset of TOML. Keys are keypad digits and values are
+/// a host key name or a list of them; `stick_threshold` sets how far, in
+/// percent, sticks must be pushed:
+///
+/// ```text
+/// [keys]
+/// 5 = ["Z", "Up", "pad:a"]
+/// stick_threshold = 30
+///
+/// [rom.8c3b0d5f21a9e4c7]
+/// 4 = ["Left", "pad:leftshoulder"]
+/// 6 = ["Right", "pad:rightshoulder"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyConfig {
+    global: Section,
+    roms: BTreeMap<u64, Section>,
+}
+
+impl KeyConfig {
+    /// Where the config lives unless `--keymap` says otherwise:
+    /// `keys.toml` in `config::config_dir`.
+    pub fn default_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join("keys.toml"))
+    }
+
+    /// Reads a config file; a missing file is an empty config.
+    pub fn load(path: &Path) -> Result<KeyConfig, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(text) => KeyConfig::parse(&text),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(KeyConfig::default()),
+            Err(error) => Err(ConfigError::Io(error)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_string())
+    }
+
+    /// The bindings to use for a ROM: the default layout, then the global
+    /// changes, then the ROM's own.
+    pub fn keymap(&self, rom_hash: u64) -> KeyMap {
+        self.layout(Some(rom_hash))
+    }
+
+    // The bindings with the global changes and, given a hash, that ROM's.
+    fn layout(&self, rom_hash: Option<u64>) -> KeyMap {
+        let mut keymap = KeyMap::default();
+        for section in Some(&self.global)
+            .into_iter()
+            .chain(rom_hash.and_then(|hash| self.roms.get(&hash)))
+        {
+            for (&keypad, hosts) in section.keys.iter() {
+                keymap.set(keypad, hosts);
+            }
+            if let Some(threshold) = section.stick_threshold {
+                keymap.stick_threshold = threshold;
+            }
+        }
+        keymap
+    }
+
+    /// Binds a keypad key to a host key, for one ROM or, with no hash, for
+    /// all of them. A keyboard key replaces the keypad key's other keyboard
+    /// keys and a controller input its other controller inputs; the other
+    /// kind stays bound.
+    pub fn bind(&mut self, rom_hash: Option<u64>, keypad: usize, host: &str) {
+        let mut hosts = vec![host.to_string()];
+        hosts.extend(
+            self.layout(rom_hash)
+                .host_keys(keypad)
+                .iter()
+                .filter(|name| is_pad(name) != is_pad(host))
+                .cloned(),
+        );
+        let section = match rom_hash {
+            Some(hash) => self.roms.entry(hash).or_default(),
+            None => &mut self.global,
+        };
+        section.keys.insert(keypad, hosts);
+    }
+
+    pub fn parse(text: &str) -> Result<KeyConfig, ConfigError> {
+        let mut config = KeyConfig::default();
+        let mut section = None;
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let invalid = |message: &str| ConfigError::Invalid(number, message.to_string());
+            let line = config::strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = match name.trim() {
+                    "keys" => Some(None),
+                    name => match name.strip_prefix("rom.") {
+                        Some(hash) => match u64::from_str_radix(hash.trim_matches('"'), 16) {
+                            Ok(hash) => Some(Some(hash)),
+                            Err(_) => return Err(invalid("expected a hex ROM hash")),
+                        },
+                        None => return Err(invalid("expected [keys] or [rom.<hash>]")),
+                    },
+                };
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected <keypad key> = <host keys>"))?;
+            let (key, value) = (key.trim().trim_matches('"'), value.trim());
+            let section = match section {
+                Some(Some(hash)) => config.roms.entry(hash).or_default(),
+                Some(None) => &mut config.global,
+                None => return Err(invalid("binding outside a section")),
+            };
+            if key == "stick_threshold" {
+                let threshold = value
+                    .parse()
+                    .ok()
+                    .filter(|threshold| (1..=100).contains(threshold))
+                    .ok_or_else(|| invalid("stick_threshold is a percentage from 1 to 100"))?;
+                section.stick_threshold = Some(threshold);
+                continue;
+            }
+            let keypad = match key {
+                key if key.len() == 1 => key.chars().next().and_then(|key| key.to_digit(16)),
+                _ => None,
+            }
+            .ok_or_else(|| invalid("keypad keys are hex digits 0-F"))?;
+            let hosts = config::parse_strings(value)
+                .ok_or_else(|| invalid("expected a quoted key name or a list of them"))?;
+            section.keys.insert(keypad as usize, hosts);
+        }
+        Ok(config)
+    }
+}
+
+impl fmt::Display for KeyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "# rust-chip8 key bindings: keypad key = SDL key or pad: names"
+        )?;
+        writeln!(f, "[keys]")?;
+        write_section(f, &self.global)?;
+        for (hash, section) in self.roms.iter() {
+            writeln!(f, "\n[rom.{:016x}]", hash)?;
+            write_section(f, section)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_section(f: &mut fmt::Formatter, section: &Section) -> fmt::Result {
+    for (keypad, hosts) in section.keys.iter() {
+        let hosts: Vec<String> = hosts.iter().map(|host| config::quote(host)).collect();
+        writeln!(f, "{:X} = [{}]", keypad, hosts.join(", "))?;
+    }
+    if let Some(threshold) = section.stick_threshold {
+        writeln!(f, "stick_threshold = {}", threshold)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[path = "./keymap_test.rs"]
+mod keymap_test;
@@ -0,0 +1,136 @@
+use super::*;
+use config::ConfigError;
+
+const HASH: u64 = 0x8c3b_0d5f_21a9_e4c7;
+
+#[test]
+fn test_default_keymap() {
+    let keymap = KeyMap::default();
+    assert_eq!(keymap.keypad_key("x"), Some(0x0));
+    assert_eq!(keymap.keypad_key("4"), Some(0xC));
+    assert_eq!(keymap.keypad_key("V"), Some(0xF));
+    assert_eq!(keymap.keypad_key("Up"), None);
+    assert_eq!(keymap.keypad_key("pad:DPUP"), Some(0x2));
+    assert_eq!(keymap.keypad_key("pad:leftx+"), Some(0x6));
+    assert_eq!(keymap.stick_threshold(), DEFAULT_STICK_THRESHOLD);
+}
+
+#[test]
+fn test_parse() {
+    let config = KeyConfig::parse(
+        "# bindings\n\
+         [keys]\n\
+         5 = [\"Z\", \"Up\"]  # move\n\
+         \n\
+         [rom.8c3b0d5f21a9e4c7]\n\
+         a = \",\"\n",
+    )
+    .unwrap();
+
+    let global = config.keymap(0);
+    assert_eq!(global.keypad_key("up"), Some(0x5));
+    assert_eq!(global.keypad_key("Z"), Some(0x5));
+    assert_eq!(global.keypad_key("W"), None);
+    assert_eq!(global.keypad_key("Q"), Some(0x4));
+
+    let rom = config.keymap(HASH);
+    assert_eq!(rom.keypad_key(","), Some(0xA));
+    // Z moved to 5 globally, so A is only bound to the ROM's key.
+    assert_eq!(rom.host_keys(0xA), [","]);
+    assert_eq!(rom.keypad_key("Up"), Some(0x5));
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |text| match KeyConfig::parse(text) {
+        Err(ConfigError::Invalid(line, _)) => line,
+        other => panic!("expected an error, got {:?}", other),
+    };
+    assert_eq!(error("5 = \"W\""), 1);
+    assert_eq!(error("[keys]\n\n10 = \"W\""), 3);
+    assert_eq!(error("[keys]\n5 = W"), 2);
+    assert_eq!(error("[keys]\n5 = [\"W\" \"E\"]"), 2);
+    assert_eq!(error("[rom.xyz]"), 1);
+    assert_eq!(error("[mouse]"), 1);
+    assert_eq!(error("[keys]\nstick_threshold = 0"), 2);
+}
+
+#[test]
+fn test_bind_and_round_trip() {
+    let mut config = KeyConfig::default();
+    config.bind(None, 0x5, "Up");
+    config.bind(Some(HASH), 0x4, "Left");
+    config.bind(Some(HASH), 0x5, "Keypad 5");
+    config.bind(Some(HASH), 0x6, "pad:rightshoulder");
+    let parsed = KeyConfig::parse(&config.to_string()).unwrap();
+    assert_eq!(parsed, config);
+
+    let keymap = parsed.keymap(HASH);
+    assert_eq!(keymap.keypad_key("Left"), Some(0x4));
+    assert_eq!(keymap.keypad_key("Q"), None);
+    assert_eq!(keymap.keypad_key("keypad 5"), Some(0x5));
+    assert_eq!(keymap.keypad_key("Up"), None);
+    assert_eq!(parsed.keymap(0).keypad_key("Up"), Some(0x5));
+}
+
+// Rebinding saves whatever name SDL gives the key: the character itself for
+// printable keys, quotes and backslashes included, or a name like "Keypad #".
+#[test]
+fn test_round_trip_every_key_name() {
+    let mut names: Vec<String> = (b'!'..=b'~').map(|c| (c as char).to_string()).collect();
+    names.extend(
+        [
+            "Keypad #",
+            "Keypad 5",
+            "Left Shift",
+            "AC Back",
+            "\u{a7}",
+            "pad:rightshoulder",
+            "pad:lefty-",
+        ]
+        .iter()
+        .map(|name| name.to_string()),
+    );
+    for name in names.iter() {
+        let mut config = KeyConfig::default();
+        config.bind(None, 0x5, name);
+        config.bind(Some(HASH), 0xA, name);
+        let text = config.to_string();
+        let parsed = KeyConfig::parse(&text).unwrap_or_else(|error| panic!("{}: {}", name, error));
+        assert_eq!(parsed, config, "{}", name);
+        assert_eq!(parsed.keymap(HASH).host_keys(0xA)[0], *name);
+    }
+
+    let config = KeyConfig::parse("[keys]\n5 = [\"\\\"\", \"\\\\\"]  # \"#\"\n").unwrap();
+    assert_eq!(config.keymap(0).host_keys(0x5), ["\"", "\\"]);
+    assert!(KeyConfig::parse("[keys]\n5 = \"\\n\"").is_err());
+}
+
+// Rebinding a keypad key from the keyboard leaves its controller inputs
+// alone, and the other way round.
+#[test]
+fn test_bind_keeps_the_other_kind() {
+    let mut config = KeyConfig::default();
+    config.bind(None, 0x5, "Space");
+    assert_eq!(config.keymap(0).host_keys(0x5), ["Space", "pad:a"]);
+    config.bind(Some(HASH), 0x5, "pad:start");
+    assert_eq!(config.keymap(HASH).host_keys(0x5), ["pad:start", "Space"]);
+    config.bind(Some(HASH), 0x5, "Return");
+    assert_eq!(config.keymap(HASH).host_keys(0x5), ["Return", "pad:start"]);
+    assert_eq!(config.keymap(0).host_keys(0x5), ["Space", "pad:a"]);
+    assert_eq!(config.keymap(0).keypad_key("W"), None);
+}
+
+#[test]
+fn test_stick_threshold() {
+    let config = KeyConfig::parse(
+        "[keys]\n\
+         stick_threshold = 30\n\
+         [rom.8c3b0d5f21a9e4c7]\n\
+         stick_threshold = 80\n",
+    )
+    .unwrap();
+    assert_eq!(config.keymap(0).stick_threshold(), 30);
+    assert_eq!(config.keymap(HASH).stick_threshold(), 80);
+    assert_eq!(KeyConfig::parse(&config.to_string()).unwrap(), config);
+}
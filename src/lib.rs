@@ -0,0 +1,51 @@
+extern crate png;
+extern crate rand;
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+pub mod assembler;
+pub mod clock;
+pub mod config;
+pub mod crt;
+pub mod debugger;
+pub mod disassembler;
+pub mod display;
+pub mod error;
+pub mod flicker;
+pub mod font;
+pub mod gif;
+pub mod headless;
+pub mod instruction;
+pub mod keymap;
+pub mod modules;
+pub mod movie;
+pub mod processor;
+pub mod quirks;
+pub mod random;
+pub mod rewind;
+pub mod savestate;
+pub mod snapshot;
+pub mod sound;
+pub mod wav;
+
+pub use clock::Clock;
+pub use error::{EmulatorError, ErrorKind};
+pub use instruction::Instruction;
+pub use modules::CartridgeModule;
+pub use processor::{OutputState, Processor};
+pub use quirks::Quirks;
+
+pub const CHIP8_WIDTH: usize = 64;
+pub const CHIP8_HEIGHT: usize = 32;
+pub const CHIP8_MEMORY: usize = 4096;
+pub const SCHIP_WIDTH: usize = 128;
+pub const SCHIP_HEIGHT: usize = 64;
+pub const XOCHIP_MEMORY: usize = 0x10000;
+
+/// Where programs are loaded and execution starts.
+pub const PROGRAM_START: usize = 0x200;
+
+/// Frame buffer sized for the largest (SUPER-CHIP high-resolution) mode.
+/// Lower resolutions use its top-left corner. Each cell holds one bit per
+/// XO-CHIP bitplane, so values range from 0 to 3.
+pub type Vram = [[u8; SCHIP_WIDTH]; SCHIP_HEIGHT];
@@ -1,46 +1,1145 @@
+#[cfg(feature = "sdl")]
 extern crate rand;
+extern crate rust_chip8;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
-mod font;
-mod modules;
-mod processor;
 
 use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::slice;
+use std::str::FromStr;
+
+use rust_chip8::assembler;
+use rust_chip8::clock::{DEFAULT_INSTRUCTIONS_PER_SECOND, TIMER_HZ};
+use rust_chip8::debugger;
+use rust_chip8::disassembler;
+use rust_chip8::display::{self, Palette};
+use rust_chip8::gif::GifRecorder;
+use rust_chip8::headless::{self, KeyScript, Limit};
+use rust_chip8::modules::CartridgeModule;
+use rust_chip8::snapshot::{self, Format};
+use rust_chip8::sound::{self, Beeper, SoundOptions, Waveform, SAMPLE_RATE};
+use rust_chip8::wav::WavWriter;
+use rust_chip8::{Clock, Processor, Quirks, PROGRAM_START};
+
+#[cfg(feature = "sdl")]
+use rust_chip8::crt::Crt;
+#[cfg(feature = "sdl")]
+use rust_chip8::debugger::{Command, Debugger};
+#[cfg(feature = "sdl")]
+use rust_chip8::display::{DisplayOptions, Rgb};
+#[cfg(feature = "sdl")]
+use rust_chip8::flicker::AntiFlicker;
+#[cfg(feature = "sdl")]
+use rust_chip8::keymap::KeyConfig;
+#[cfg(feature = "sdl")]
+use rust_chip8::modules::{DisplayModule, Hotkey, InputModule, SoundModule};
+#[cfg(feature = "sdl")]
+use rust_chip8::movie::Movie;
+#[cfg(feature = "sdl")]
+use rust_chip8::rewind::Rewind;
+#[cfg(feature = "sdl")]
+use rust_chip8::savestate;
+#[cfg(feature = "sdl")]
+use rust_chip8::{EmulatorError, OutputState};
+#[cfg(feature = "sdl")]
+use std::io::BufRead;
+#[cfg(feature = "sdl")]
+use std::sync::mpsc;
+#[cfg(feature = "sdl")]
 use std::thread;
-use std::time::Duration;
+#[cfg(feature = "sdl")]
+use std::time::{Duration, Instant};
+
+// Ten seconds, unless --frames or --cycles says otherwise.
+const HEADLESS_FRAMES: u64 = 600;
+
+// Samples of sound in each 60 Hz frame.
+const FRAME_SAMPLES: usize = (SAMPLE_RATE / TIMER_HZ) as usize;
+
+// How many times real time fast-forward runs at.
+#[cfg(feature = "sdl")]
+const FAST_FORWARD_SPEED: u32 = 4;
+
+#[cfg(feature = "sdl")]
+const VOLUME_STEP: f32 = 0.05;
+
+#[cfg(feature = "sdl")]
+struct Options {
+    cartridge_filename: String,
+    instructions_per_second: u32,
+    quirks: Quirks,
+    debug: bool,
+    seed: Option<u64>,
+    record: Option<PathBuf>,
+    play: Option<PathBuf>,
+    keymap: Option<PathBuf>,
+    // Display settings given on the command line, which win over the config
+    // file.
+    palette: Option<Palette>,
+    foreground: Option<Rgb>,
+    background: Option<Rgb>,
+    scale: Option<u32>,
+    fullscreen: bool,
+    anti_flicker: Option<AntiFlicker>,
+    vsync: bool,
+    crt: Option<Crt>,
+    sound: SoundArgs,
+}
+
+// Sound settings given on the command line, which win over sound.toml.
+#[derive(Default)]
+struct SoundArgs {
+    tone: Option<f32>,
+    waveform: Option<Waveform>,
+    volume: Option<f32>,
+    mute: bool,
+}
+
+impl SoundArgs {
+    // Takes arg, and the value after it, if it's a sound setting.
+    fn parse(&mut self, arg: &str, args: &mut slice::Iter<String>) -> bool {
+        match arg {
+            "--tone" => {
+                self.tone = Some(
+                    args.next()
+                        .and_then(|hz| sound::parse_frequency(hz))
+                        .unwrap_or_else(|| usage()),
+                )
+            }
+            "--waveform" => {
+                self.waveform = Some(
+                    args.next()
+                        .and_then(|name| Waveform::parse(name))
+                        .unwrap_or_else(|| usage()),
+                )
+            }
+            "--volume" => {
+                self.volume = Some(
+                    args.next()
+                        .and_then(|percent| sound::parse_volume(percent))
+                        .unwrap_or_else(|| usage()),
+                )
+            }
+            "--mute" => self.mute = true,
+            _ => return false,
+        }
+        true
+    }
+
+    // Reads sound.toml, then applies the command line on top.
+    fn options(&self) -> SoundOptions {
+        let path = SoundOptions::default_path();
+        let mut sound = match path.as_ref().map(|path| SoundOptions::load(path)) {
+            Some(Ok(sound)) => sound,
+            Some(Err(error)) => {
+                let path = path.as_ref().unwrap();
+                eprintln!("ignoring sound settings in {}: {}", path.display(), error);
+                SoundOptions::default()
+            }
+            None => SoundOptions::default(),
+        };
+        sound.frequency = self.tone.unwrap_or(sound.frequency);
+        sound.waveform = self.waveform.unwrap_or(sound.waveform);
+        sound.volume = self.volume.unwrap_or(sound.volume);
+        sound.muted |= self.mute;
+        sound
+    }
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: rust-chip8 [--ips <instructions per second>] [--quirks legacy|vip|chip48|schip|xochip] [--debug]
+                  [--seed <n>] [--record <movie> | --play <movie>] [--keymap <file>]
+                  [--palette <name>] [--fg <#rrggbb>] [--bg <#rrggbb>] [--scale <n>] [--fullscreen]
+                  [--anti-flicker off|phosphor|or|average[:<frames>]] [--vsync]
+                  [--crt off|on|scanlines=N,grid=N,bloom=N,curvature=N]
+                  [--tone <hz>] [--waveform square|sine|triangle|noise] [--volume <percent>]
+                  [--mute] <rom>
+       rust-chip8 disasm <rom>
+       rust-chip8 asm <source> [<rom>]
+       rust-chip8 headless [--ips <n>] [--quirks <name>] [--seed <n>] [--frames <n> | --cycles <n>]
+                           [--keys <frame:keys ...>] [--out <file.png|file.pbm|file.txt>]
+                           [--gif <file>] [--wav <file>] [--scale <n>] [--palette <name>]
+                           [--tone <hz>] [--waveform <name>] [--volume <percent>] [--mute] <rom>"
+    );
+    process::exit(2);
+}
+
+fn parse_positive<T: FromStr + PartialOrd + Default>(value: Option<&String>) -> T {
+    match value.map(|value| value.parse()) {
+        Some(Ok(value)) if value > T::default() => value,
+        _ => usage(),
+    }
+}
+
+fn parse_quirks(value: Option<&String>) -> Quirks {
+    match value.and_then(|name| Quirks::from_name(name)) {
+        Some(quirks) => quirks,
+        None => usage(),
+    }
+}
+
+fn parse_seed(value: Option<&String>) -> u64 {
+    match value.map(|value| value.parse()) {
+        Some(Ok(seed)) => seed,
+        _ => usage(),
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn parse_color(value: Option<&String>) -> Rgb {
+    match value.and_then(|color| display::parse_color(color)) {
+        Some(color) => color,
+        None => usage(),
+    }
+}
+
+fn parse_palette(value: Option<&String>) -> Palette {
+    match value.and_then(|name| display::palette(name)) {
+        Some(palette) => palette,
+        None => {
+            let names: Vec<&str> = display::PALETTES.iter().map(|&(name, _)| name).collect();
+            eprintln!("palettes: {}", names.join(", "));
+            usage()
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn parse_args(args: &[String]) -> Options {
+    let mut cartridge_filename = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut quirks = Quirks::default();
+    let mut debug = false;
+    let mut seed = None;
+    let mut record = None;
+    let mut play = None;
+    let mut keymap = None;
+    let mut palette = None;
+    let mut foreground = None;
+    let mut background = None;
+    let mut scale = None;
+    let mut fullscreen = false;
+    let mut anti_flicker = None;
+    let mut vsync = false;
+    let mut crt = None;
+    let mut sound = SoundArgs::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ips" => instructions_per_second = parse_positive(args.next()),
+            "--quirks" => quirks = parse_quirks(args.next()),
+            "--debug" => debug = true,
+            "--seed" => seed = Some(parse_seed(args.next())),
+            "--record" => record = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--play" => play = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--keymap" => keymap = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--palette" => palette = Some(parse_palette(args.next())),
+            "--fg" => foreground = Some(parse_color(args.next())),
+            "--bg" => background = Some(parse_color(args.next())),
+            "--scale" => scale = Some(parse_positive(args.next())),
+            "--fullscreen" => fullscreen = true,
+            "--anti-flicker" => {
+                anti_flicker = match args.next().and_then(|mode| AntiFlicker::parse(mode)) {
+                    Some(mode) => Some(mode),
+                    None => usage(),
+                }
+            }
+            "--vsync" => vsync = true,
+            "--crt" => {
+                crt = Some(
+                    args.next()
+                        .and_then(|crt| Crt::parse(crt))
+                        .unwrap_or_else(|| usage()),
+                )
+            }
+            _ if sound.parse(arg, &mut args) => {}
+            _ if cartridge_filename.is_none() => cartridge_filename = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
 
-use modules::{CartridgeModule, DisplayModule, InputModule, SoundModule};
+    // A movie only replays if every frame ran the same way, which stepping in
+    // the debugger would break.
+    let movies = record.is_some() as u8 + play.is_some() as u8;
+    // A movie brings its own seed.
+    if movies > 1 || (movies == 1 && debug) || (play.is_some() && seed.is_some()) {
+        usage();
+    }
+
+    Options {
+        cartridge_filename: cartridge_filename.unwrap_or_else(|| usage()),
+        instructions_per_second,
+        quirks,
+        debug,
+        seed,
+        record,
+        play,
+        keymap,
+        palette,
+        foreground,
+        background,
+        scale,
+        fullscreen,
+        anti_flicker,
+        vsync,
+        crt,
+        sound,
+    }
+}
+
+#[cfg(feature = "sdl")]
+// Reads display.toml, then applies the command line on top.
+fn display_options(options: &Options) -> DisplayOptions {
+    let path = DisplayOptions::default_path();
+    let mut display = match path.as_ref().map(|path| DisplayOptions::load(path)) {
+        Some(Ok(display)) => display,
+        Some(Err(error)) => {
+            let path = path.as_ref().unwrap();
+            eprintln!("ignoring display settings in {}: {}", path.display(), error);
+            DisplayOptions::default()
+        }
+        None => DisplayOptions::default(),
+    };
+    display.palette = options.palette.unwrap_or(display.palette);
+    display.palette[1] = options.foreground.unwrap_or(display.palette[1]);
+    display.palette[0] = options.background.unwrap_or(display.palette[0]);
+    display.scale = options.scale.unwrap_or(display.scale);
+    display.fullscreen |= options.fullscreen;
+    display.anti_flicker = options.anti_flicker.unwrap_or(display.anti_flicker);
+    display.vsync |= options.vsync;
+    display.crt = options.crt.unwrap_or(display.crt);
+    display
+}
+
+#[cfg(feature = "sdl")]
+// Reads debugger commands from stdin on a separate thread, so the window
+// keeps running while the user types. An empty line repeats the last command.
+fn spawn_debugger_repl() -> mpsc::Receiver<Command> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        let mut last = None;
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            let command = if line.trim().is_empty() {
+                match last {
+                    Some(command) => command,
+                    None => continue,
+                }
+            } else {
+                match debugger::parse_command(&line) {
+                    Ok(command) => command,
+                    Err(message) => {
+                        eprintln!("{}", message);
+                        continue;
+                    }
+                }
+            };
+            last = Some(command);
+            if sender.send(command).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn disasm(args: &[String]) {
+    let filename = match args {
+        [filename] => filename,
+        _ => usage(),
+    };
+    let cartridge = CartridgeModule::new(filename);
+    println!(
+        "{}",
+        disassembler::disassemble(&cartridge.rom, PROGRAM_START)
+    );
+}
+
+// Writes the ROM next to the source unless told otherwise.
+fn asm(args: &[String]) {
+    let (source, rom) = match args {
+        [source] => (Path::new(source), Path::new(source).with_extension("ch8")),
+        [source, rom] => (Path::new(source), PathBuf::from(rom)),
+        _ => usage(),
+    };
+    let assembled = match assembler::assemble_file(source) {
+        Ok(assembled) => assembled,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
+    if let Err(error) = fs::write(&rom, assembled) {
+        eprintln!("could not write {}: {}", rom.display(), error);
+        process::exit(1);
+    }
+}
+
+// Creates a file to write a capture to, buffered.
+fn create(path: &Path) -> io::Result<io::BufWriter<fs::File>> {
+    fs::File::create(path).map(io::BufWriter::new)
+}
+
+// The animation and sound a headless run is recording, for --gif and --wav.
+struct Capture {
+    gif_path: Option<PathBuf>,
+    gif: Option<GifRecorder<io::BufWriter<fs::File>>>,
+    wav: Option<WavWriter<io::BufWriter<fs::File>>>,
+    beeper: Beeper,
+    samples: Vec<f32>,
+    palette: Palette,
+    scale: usize,
+}
+
+impl Capture {
+    fn new(
+        gif_path: Option<PathBuf>,
+        wav_path: Option<&Path>,
+        sound: SoundOptions,
+        palette: Palette,
+        scale: usize,
+    ) -> io::Result<Capture> {
+        let wav = match wav_path {
+            Some(path) => Some(WavWriter::new(create(path)?, SAMPLE_RATE)?),
+            None => None,
+        };
+        let mut beeper = Beeper::new(sound, SAMPLE_RATE);
+        // Nothing plays live, so there's no jitter to absorb.
+        beeper.set_latency(0.0);
+        Ok(Capture {
+            gif_path,
+            gif: None,
+            wav,
+            beeper,
+            samples: vec![0.0; FRAME_SAMPLES],
+            palette,
+            scale,
+        })
+    }
 
-use processor::Processor;
-const CHIP8_WIDTH: usize = 64;
-const CHIP8_HEIGHT: usize = 32;
-const CHIP8_MEMORY: usize = 4096;
+    // Records the frame just run. The GIF is sized after the first frame,
+    // by when SUPER-CHIP programs have usually switched to high resolution.
+    fn frame(&mut self, processor: &mut Processor) -> io::Result<()> {
+        let (width, height) = (processor.width(), processor.height());
+        if let Some(ref path) = self.gif_path {
+            if self.gif.is_none() {
+                let (gif_width, gif_height) = (width * self.scale, height * self.scale);
+                self.gif = Some(GifRecorder::new(
+                    create(path)?,
+                    gif_width,
+                    gif_height,
+                    &self.palette,
+                )?);
+            }
+        }
+        if let Some(ref mut gif) = self.gif {
+            gif.push(processor.vram(), width, height)?;
+        }
+        if let Some(ref mut wav) = self.wav {
+            let output = processor.output(false);
+            self.beeper.set_pattern(output.audio_pattern, output.pitch);
+            let sound = processor.take_sound();
+            self.beeper.push(&sound, 1.0 / TIMER_HZ as f32);
+            self.beeper.fill(&mut self.samples);
+            wav.write(&self.samples)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<()> {
+        if let Some(gif) = self.gif {
+            gif.finish()?;
+        }
+        if let Some(wav) = self.wav {
+            wav.finish()?;
+        }
+        Ok(())
+    }
+}
+
+// Runs a ROM without a window, then writes out the framebuffer and dumps the
+// registers.
+fn headless(args: &[String]) {
+    let mut cartridge_filename = None;
+    let mut instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+    let mut quirks = Quirks::default();
+    let mut limit = Limit::Frames(HEADLESS_FRAMES);
+    let mut keys = KeyScript::default();
+    let mut seed = None;
+    let mut out = None;
+    let mut gif = None;
+    let mut wav = None;
+    let mut scale = 1;
+    let mut palette = snapshot::PALETTE;
+    let mut sound = SoundArgs::default();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ips" => instructions_per_second = parse_positive(args.next()),
+            "--quirks" => quirks = parse_quirks(args.next()),
+            "--frames" => limit = Limit::Frames(parse_positive(args.next())),
+            "--cycles" => limit = Limit::Cycles(parse_positive(args.next())),
+            "--seed" => seed = Some(parse_seed(args.next())),
+            "--keys" => {
+                keys = match args.next().map(|script| KeyScript::parse(script)) {
+                    Some(Ok(keys)) => keys,
+                    Some(Err(message)) => {
+                        eprintln!("{}", message);
+                        usage()
+                    }
+                    None => usage(),
+                }
+            }
+            "--out" => match args.next() {
+                Some(path) => out = Some(PathBuf::from(path)),
+                None => usage(),
+            },
+            "--gif" => gif = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--wav" => wav = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            "--scale" => scale = parse_positive(args.next()),
+            "--palette" => palette = parse_palette(args.next()),
+            _ if sound.parse(arg, &mut args) => {}
+            _ if cartridge_filename.is_none() => cartridge_filename = Some(arg.clone()),
+            _ => usage(),
+        }
+    }
+
+    let cartridge_filename = cartridge_filename.unwrap_or_else(|| usage());
+    let cartridge = CartridgeModule::new(&cartridge_filename);
+    let mut processor = Processor::new(quirks);
+    let mut clock = Clock::new(instructions_per_second);
+    processor.load(&cartridge.rom);
+    if let Some(seed) = seed {
+        processor.seed(seed);
+    }
+
+    let sound = match wav {
+        Some(_) => sound.options(),
+        None => SoundOptions::default(),
+    };
+    let mut capture = match Capture::new(gif, wav.as_deref(), sound, palette, scale) {
+        Ok(capture) => capture,
+        Err(error) => {
+            eprintln!("could not start recording: {}", error);
+            process::exit(1);
+        }
+    };
+    let mut captured = Ok(());
+    // Dump the final state even when the ROM faults; that's when it's needed.
+    let result = headless::run_with(&mut processor, &mut clock, limit, &keys, |processor| {
+        if captured.is_ok() {
+            captured = capture.frame(processor);
+        }
+    });
+    if let Err(error) = captured.and_then(|()| capture.finish()) {
+        eprintln!("could not write the recording: {}", error);
+        process::exit(1);
+    }
+    let (vram, width, height) = (processor.vram(), processor.width(), processor.height());
+    let written = match out {
+        Some(ref path) => create(path).and_then(|file| match Format::from_path(path) {
+            Format::Png => snapshot::write_scaled_png(file, vram, width, height, &palette, scale),
+            format => snapshot::write(file, format, vram, width, height),
+        }),
+        None => snapshot::write_ascii(io::stdout(), vram, width, height),
+    };
+    if let Err(error) = written {
+        eprintln!("could not write the framebuffer: {}", error);
+        process::exit(1);
+    }
+    println!("{}", debugger::registers(&processor));
+    if let Err(error) = result {
+        eprintln!("{}: {}", cartridge_filename, error);
+        process::exit(1);
+    }
+}
 
 fn main() {
-    let sleep_duration = Duration::from_millis(2);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Some((command, rest)) = args.split_first() {
+        match command.as_str() {
+            "disasm" => return disasm(rest),
+            "asm" => return asm(rest),
+            "headless" => return headless(rest),
+            _ => {}
+        }
+    }
+
+    #[cfg(feature = "sdl")]
+    run_window(parse_args(&args));
+    #[cfg(not(feature = "sdl"))]
+    {
+        eprintln!("built without the sdl feature; only asm, disasm and headless are available");
+        process::exit(2);
+    }
+}
+
+#[cfg(feature = "sdl")]
+// Screenshots and recordings go next to the ROM, named after the frame they
+// start on: pong.ch8.frame600.png.
+fn capture_path(cartridge_filename: &str, clock: &Clock, extension: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}.frame{}.{}",
+        cartridge_filename,
+        clock.frames(),
+        extension
+    ))
+}
+
+#[cfg(feature = "sdl")]
+fn save_recording<W>(path: &Path, finished: io::Result<W>) {
+    match finished {
+        Ok(_) => println!("saved recording to {}", path.display()),
+        Err(error) => eprintln!("could not save {}: {}", path.display(), error),
+    }
+}
+
+#[cfg(feature = "sdl")]
+// Runs one frame, under the debugger if it's attached.
+fn run_frame<'a>(
+    processor: &'a mut Processor,
+    keypad: [bool; 16],
+    clock: &mut Clock,
+    debugger: Option<&mut Debugger>,
+) -> Result<OutputState<'a>, EmulatorError> {
+    let debugger = match debugger {
+        Some(debugger) => debugger,
+        None => return processor.run_frame(keypad, clock),
+    };
+    let (vram_changed, stop) = debugger.run_frame(processor, keypad, clock);
+    if let Some(reason) = stop {
+        println!("{}", debugger::describe_stop(&reason, processor));
+    }
+    Ok(processor.output(vram_changed))
+}
+
+#[cfg(feature = "sdl")]
+// The window's drivers and the emulator state its hotkeys change.
+struct Window {
+    options: Options,
+    display: DisplayOptions,
+    cartridge: CartridgeModule,
+    display_driver: DisplayModule,
+    input_driver: InputModule,
+    sound_driver: SoundModule,
+    processor: Processor,
+    clock: Clock,
+    rewind: Rewind,
+    playback: Option<Movie>,
+    recording: Option<Movie>,
+    key_config: KeyConfig,
+    keymap_path: Option<PathBuf>,
+    // Whose bindings the rebinding hotkey is changing: this ROM's, or with no
+    // hash, every ROM's.
+    rebinding: Option<u64>,
+    paused: bool,
+    // Run a single frame even though paused.
+    advance: bool,
+    fast_forward: bool,
+    unpresented: bool,
+    // Recordings started by hotkeys, and where they're going.
+    gif: Option<(GifRecorder<io::BufWriter<fs::File>>, PathBuf)>,
+    wav: Option<(WavWriter<io::BufWriter<fs::File>>, PathBuf)>,
+}
+
+#[cfg(feature = "sdl")]
+impl Window {
+    fn new(sdl_context: &sdl2::Sdl, options: Options) -> Window {
+        let cartridge = CartridgeModule::new(&options.cartridge_filename);
+        let display = display_options(&options);
+        let display_driver = DisplayModule::new(sdl_context, &display);
+        let mut input_driver = InputModule::new(sdl_context);
+        let sound_driver = SoundModule::new(sdl_context, options.sound.options());
+        let mut processor = Processor::new(options.quirks);
+        let mut clock = Clock::new(options.instructions_per_second);
+
+        processor.load(&cartridge.rom);
+        if let Some(seed) = options.seed {
+            processor.seed(seed);
+        }
+
+        let mut playback = None;
+        let mut recording = None;
+        if let Some(ref path) = options.play {
+            let started = Movie::load(path).and_then(|movie| {
+                let (movie_processor, movie_clock) = movie.start(&cartridge.rom)?;
+                processor = movie_processor;
+                clock = movie_clock;
+                Ok(movie)
+            });
+            match started {
+                Ok(movie) => playback = Some(movie),
+                Err(error) => {
+                    eprintln!("could not play {}: {}", path.display(), error);
+                    process::exit(1);
+                }
+            }
+        } else if options.record.is_some() {
+            let seed = options.seed.unwrap_or_else(rand::random);
+            processor.seed(seed);
+            recording = Some(Movie::new(
+                &cartridge.rom,
+                seed,
+                options.instructions_per_second,
+                options.quirks,
+            ));
+        }
+
+        let keymap_path = options.keymap.clone().or_else(KeyConfig::default_path);
+        let key_config = match keymap_path.as_ref().map(|path| KeyConfig::load(path)) {
+            Some(Ok(config)) => config,
+            Some(Err(error)) => {
+                let path = keymap_path.as_ref().unwrap();
+                eprintln!("ignoring key bindings in {}: {}", path.display(), error);
+                KeyConfig::default()
+            }
+            None => KeyConfig::default(),
+        };
+        for name in input_driver.set_keymap(&key_config.keymap(cartridge.hash())) {
+            eprintln!("unknown key name in key bindings: {}", name);
+        }
+
+        Window {
+            options,
+            display,
+            cartridge,
+            display_driver,
+            input_driver,
+            sound_driver,
+            processor,
+            clock,
+            rewind: Rewind::default(),
+            playback,
+            recording,
+            key_config,
+            keymap_path,
+            rebinding: None,
+            paused: false,
+            advance: false,
+            fast_forward: false,
+            unpresented: false,
+            gif: None,
+            wav: None,
+        }
+    }
+
+    // Rewinding, resetting or loading a state would desync a movie from its
+    // recorded input.
+    fn movie_active(&self) -> bool {
+        self.playback.is_some() || self.recording.is_some()
+    }
+
+    // Host time one emulated frame takes.
+    fn frame_duration(&self) -> Duration {
+        let frame_duration = Duration::from_secs(1) / TIMER_HZ;
+        if self.fast_forward {
+            frame_duration / FAST_FORWARD_SPEED
+        } else {
+            frame_duration
+        }
+    }
+
+    fn redraw(&mut self) {
+        let processor = &self.processor;
+        self.display_driver
+            .draw(processor.vram(), processor.width(), processor.height());
+    }
+
+    // Acts on a hotkey. Returns false to quit.
+    fn hotkey(&mut self, hotkey: Hotkey) -> bool {
+        match hotkey {
+            Hotkey::Quit => return false,
+            Hotkey::Pause => {
+                self.paused = !self.paused;
+                println!("{}", if self.paused { "paused" } else { "resumed" });
+            }
+            Hotkey::FrameAdvance => {
+                self.paused = true;
+                self.advance = true;
+            }
+            Hotkey::FastForward => {
+                self.fast_forward = !self.fast_forward;
+                println!(
+                    "fast-forward {}",
+                    if self.fast_forward { "on" } else { "off" }
+                );
+            }
+            Hotkey::SoftReset | Hotkey::HardReset if self.movie_active() => {
+                eprintln!("can't reset while a movie is recording or playing");
+            }
+            Hotkey::SoftReset => {
+                self.processor.reset();
+                self.redraw();
+                println!("reset");
+            }
+            Hotkey::HardReset => {
+                self.processor = Processor::new(self.options.quirks);
+                self.processor.load(&self.cartridge.rom);
+                if let Some(seed) = self.options.seed {
+                    self.processor.seed(seed);
+                }
+                self.clock = Clock::new(self.options.instructions_per_second);
+                self.rewind.clear();
+                self.redraw();
+                println!("reloaded {}", self.options.cartridge_filename);
+            }
+            Hotkey::Screenshot => {
+                let path = capture_path(&self.options.cartridge_filename, &self.clock, "png");
+                let written = create(&path).and_then(|file| {
+                    snapshot::write_scaled_png(
+                        file,
+                        self.processor.vram(),
+                        self.processor.width(),
+                        self.processor.height(),
+                        &self.display.palette,
+                        self.display.scale as usize,
+                    )
+                });
+                match written {
+                    Ok(()) => println!("saved screenshot to {}", path.display()),
+                    Err(error) => eprintln!("could not save {}: {}", path.display(), error),
+                }
+            }
+            Hotkey::ToggleGif => self.toggle_gif(),
+            Hotkey::ToggleWav => self.toggle_wav(),
+            Hotkey::ToggleCrt => {
+                let on = self.display_driver.toggle_crt();
+                println!("CRT effects {}", if on { "on" } else { "off" });
+                self.unpresented = true;
+            }
+            Hotkey::CrtStronger | Hotkey::CrtWeaker => {
+                let strength = self
+                    .display_driver
+                    .adjust_crt(hotkey == Hotkey::CrtStronger);
+                println!("CRT effects at {}%", strength);
+                self.unpresented = true;
+            }
+            Hotkey::VolumeUp | Hotkey::VolumeDown => {
+                let step = if hotkey == Hotkey::VolumeUp {
+                    VOLUME_STEP
+                } else {
+                    -VOLUME_STEP
+                };
+                let sound = self.sound_driver.options();
+                self.sound_driver.set_options(SoundOptions {
+                    volume: sound.volume + step,
+                    ..sound
+                });
+                println!("volume {:.0}%", self.sound_driver.options().volume * 100.0);
+            }
+            Hotkey::Mute => {
+                let sound = self.sound_driver.options();
+                self.sound_driver.set_options(SoundOptions {
+                    muted: !sound.muted,
+                    ..sound
+                });
+                println!("sound {}", if sound.muted { "on" } else { "muted" });
+            }
+            Hotkey::Waveform => {
+                let sound = self.sound_driver.options();
+                let waveform = sound.waveform.next();
+                self.sound_driver
+                    .set_options(SoundOptions { waveform, ..sound });
+                println!("{} wave", waveform.name());
+            }
+            Hotkey::ToneUp | Hotkey::ToneDown => {
+                let semitone = 2f32.powf(1.0 / 12.0);
+                let sound = self.sound_driver.options();
+                self.sound_driver.set_options(SoundOptions {
+                    frequency: if hotkey == Hotkey::ToneUp {
+                        sound.frequency * semitone
+                    } else {
+                        sound.frequency / semitone
+                    },
+                    ..sound
+                });
+                println!("tone {:.0} Hz", self.sound_driver.options().frequency);
+            }
+            Hotkey::SaveState(slot) => {
+                let rom_path = Path::new(&self.options.cartridge_filename);
+                match savestate::save_slot(&self.processor, rom_path, slot) {
+                    Ok(path) => println!("saved state to {}", path.display()),
+                    Err(error) => eprintln!("could not save slot {}: {}", slot, error),
+                }
+            }
+            Hotkey::LoadState(_) if self.movie_active() => {
+                eprintln!("states can't be loaded while a movie is recording or playing");
+            }
+            Hotkey::LoadState(slot) => {
+                let rom_path = Path::new(&self.options.cartridge_filename);
+                match savestate::load_slot(&mut self.processor, rom_path, slot) {
+                    Ok(path) => {
+                        println!("loaded state from {}", path.display());
+                        self.redraw();
+                    }
+                    Err(error) => eprintln!("could not load slot {}: {}", slot, error),
+                }
+            }
+            Hotkey::Rebind { all_roms } => {
+                self.rebinding = if all_roms {
+                    None
+                } else {
+                    Some(self.cartridge.hash())
+                };
+                println!(
+                    "rebinding {}: press the key for keypad 0 (Escape keeps it)",
+                    if all_roms { "all ROMs" } else { "this ROM" }
+                );
+            }
+            Hotkey::Bind(keypad, host) => {
+                if let Some(host) = host {
+                    self.key_config.bind(self.rebinding, keypad, &host);
+                }
+                if keypad < 0xF {
+                    println!("press the key for keypad {:X}", keypad + 1);
+                    return true;
+                }
+                self.input_driver
+                    .set_keymap(&self.key_config.keymap(self.cartridge.hash()));
+                match self.keymap_path {
+                    Some(ref path) => match self.key_config.save(path) {
+                        Ok(()) => println!("saved key bindings to {}", path.display()),
+                        Err(error) => eprintln!("could not save {}: {}", path.display(), error),
+                    },
+                    None => println!("key bindings changed for this session"),
+                }
+            }
+        }
+        true
+    }
+
+    fn toggle_gif(&mut self) {
+        if let Some((recorder, path)) = self.gif.take() {
+            return save_recording(&path, recorder.finish());
+        }
+        let path = capture_path(&self.options.cartridge_filename, &self.clock, "gif");
+        let scale = self.display.scale as usize;
+        let started = create(&path).and_then(|file| {
+            GifRecorder::new(
+                file,
+                self.processor.width() * scale,
+                self.processor.height() * scale,
+                &self.display.palette,
+            )
+        });
+        match started {
+            Ok(recorder) => {
+                println!("recording {}", path.display());
+                self.gif = Some((recorder, path));
+            }
+            Err(error) => eprintln!("could not record {}: {}", path.display(), error),
+        }
+    }
+
+    fn toggle_wav(&mut self) {
+        if let Some((mut writer, path)) = self.wav.take() {
+            let written = writer.write(&self.sound_driver.stop_capture());
+            return save_recording(&path, written.and_then(|()| writer.finish()));
+        }
+        let path = capture_path(&self.options.cartridge_filename, &self.clock, "wav");
+        let started =
+            create(&path).and_then(|file| WavWriter::new(file, self.sound_driver.sample_rate()));
+        match started {
+            Ok(writer) => {
+                self.sound_driver.start_capture();
+                println!("recording {}", path.display());
+                self.wav = Some((writer, path));
+            }
+            Err(error) => eprintln!("could not record {}: {}", path.display(), error),
+        }
+    }
+
+    // Adds the frame to any GIF and WAV being recorded and plays its sound.
+    // shown is whether the frame was new on screen, running whether emulated
+    // time moved on.
+    fn capture_and_play(&mut self, shown: bool, running: bool) {
+        let output = self.processor.output(false);
+        // The GIF keeps every frame shown, including rewinding, at 60 Hz. It
+        // takes the raw framebuffer, since anti-flicker blends colours the
+        // four-colour palette doesn't have.
+        if shown {
+            if let Some((ref mut recorder, ref path)) = self.gif {
+                if let Err(error) = recorder.push(output.vram, output.width, output.height) {
+                    eprintln!("stopped recording {}: {}", path.display(), error);
+                    self.gif = None;
+                }
+            }
+        }
+        if let Some((ref mut writer, ref path)) = self.wav {
+            if let Err(error) = writer.write(&self.sound_driver.take_capture()) {
+                eprintln!("stopped recording {}: {}", path.display(), error);
+                self.sound_driver.stop_capture();
+                self.wav = None;
+            }
+        }
+
+        self.sound_driver
+            .set_pattern(output.audio_pattern, output.pitch);
+        // Audio plays the frame's sound timeline in the host time the frame
+        // takes, and falls silent when no frames come.
+        let sound = self.processor.take_sound();
+        if running {
+            let seconds = self.frame_duration().as_secs_f32();
+            self.sound_driver.push(&sound, seconds);
+        }
+    }
+
+    // Saves whatever is still recording.
+    fn finish(mut self) {
+        if let Some((recorder, path)) = self.gif.take() {
+            save_recording(&path, recorder.finish());
+        }
+        if let Some((mut writer, path)) = self.wav.take() {
+            let written = writer.write(&self.sound_driver.stop_capture());
+            save_recording(&path, written.and_then(|()| writer.finish()));
+        }
+        // Keep the movie even when the ROM faulted; it's the bug report.
+        if let (Some(movie), Some(path)) = (self.recording, self.options.record) {
+            match movie.save(&path) {
+                Ok(()) => println!("recorded {} frames to {}", movie.len(), path.display()),
+                Err(error) => eprintln!("could not save {}: {}", path.display(), error),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sdl")]
+fn run_window(options: Options) {
     let sdl_context = sdl2::init().unwrap();
-    let args: Vec<String> = env::args().collect();
-    let cartridge_filename = &args[1];
-    let cartridge_driver = CartridgeModule::new(cartridge_filename);
-    let mut display_driver = DisplayModule::new(&sdl_context);
-    let mut input_driver = InputModule::new(&sdl_context);
-    let sound_driver = SoundModule::new(&sdl_context);
-    let mut processor = Processor::new();
+    let debug = options.debug;
+    let mut window = Window::new(&sdl_context, options);
 
-    processor.load(&cartridge_driver.rom);
+    // Only --debug attaches the debugger, which starts paused.
+    let (mut debugger, debugger_commands) = if debug {
+        println!(
+            "debugger paused at {:03X}; type help for commands",
+            window.processor.pc()
+        );
+        (Some(Debugger::new()), Some(spawn_debugger_repl()))
+    } else {
+        (None, None)
+    };
 
-    while let Ok(keypad) = input_driver.poll() {
-        let output = processor.tick(keypad);
+    let mut next_frame = Instant::now();
+    let mut failed = false;
+    // Frames run since the window was last presented.
+    let mut frames_since_present = 0;
+    'running: loop {
+        let input = window.input_driver.poll();
+        for name in input.connected.iter() {
+            println!("controller connected: {}", name);
+        }
+        window.advance = false;
+        for hotkey in input.hotkeys {
+            if !window.hotkey(hotkey) {
+                break 'running;
+            }
+        }
 
-        if output.vram_changed {
-            display_driver.draw(output.vram);
+        // Answer debugger commands even while paused or rewinding.
+        if let (Some(debugger), Some(commands)) = (debugger.as_mut(), debugger_commands.as_ref()) {
+            for command in commands.try_iter() {
+                let text = debugger.execute(command, &window.processor);
+                if !text.is_empty() {
+                    println!("{}", text);
+                }
+            }
         }
 
-        if output.beep {
-            sound_driver.start_beep();
+        let rewinding = input.rewinding && !window.movie_active();
+        let running = !rewinding && (!window.paused || window.advance);
+
+        let output = if rewinding {
+            let stepped = window.rewind.step_back(&mut window.processor);
+            Ok(window.processor.output(stepped))
+        } else if !running {
+            Ok(window.processor.output(false))
         } else {
-            sound_driver.stop_beep();
+            // Live input takes over once the movie runs out.
+            let keypad = match window.playback {
+                Some(ref movie) => match movie.keypad(window.clock.frames()) {
+                    Some(keypad) => keypad,
+                    None => {
+                        println!("movie finished after {} frames", movie.len());
+                        window.playback = None;
+                        input.keypad
+                    }
+                },
+                None => input.keypad,
+            };
+            if let Some(ref mut movie) = window.recording {
+                movie.record(keypad);
+            }
+            window.rewind.record(&window.processor);
+            run_frame(
+                &mut window.processor,
+                keypad,
+                &mut window.clock,
+                debugger.as_mut(),
+            )
+        };
+        let output = match output {
+            Ok(output) => output,
+            Err(error) => {
+                eprintln!("{}: {}", window.options.cartridge_filename, error);
+                failed = true;
+                break;
+            }
+        };
+
+        if output.exited {
+            break;
+        }
+
+        let display_driver = &mut window.display_driver;
+        if output.vram_changed || (display_driver.filtering() && (running || rewinding)) {
+            display_driver.update(output.vram, output.width, output.height);
+            window.unpresented = true;
+        }
+        window.unpresented |= input.redraw;
+        if running {
+            frames_since_present += 1;
+        }
+        // With vsync, fast-forward presents only every FAST_FORWARD_SPEED-th
+        // frame, one per vblank, so it doesn't wait for the monitor on every
+        // frame.
+        let skip = window.display.vsync && window.fast_forward && running;
+        if window.unpresented && (!skip || frames_since_present >= FAST_FORWARD_SPEED) {
+            display_driver.present();
+            window.unpresented = false;
+            frames_since_present = 0;
         }
 
-        thread::sleep(sleep_duration);
+        window.capture_and_play(
+            running || rewinding,
+            running && !debugger::halted(debugger.as_ref()),
+        );
+
+        // Pace emulated frames to the host, dropping the backlog rather than
+        // racing to catch up if we fall more than a frame behind.
+        let frame_duration = window.frame_duration();
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else if now - next_frame > frame_duration {
+            next_frame = now;
+        }
+    }
+
+    window.finish();
+    if failed {
+        process::exit(1);
     }
 }
@@ -1,25 +1,41 @@
 use std::fs::File;
 use std::io::prelude::*;
 
+use PROGRAM_START;
+use XOCHIP_MEMORY;
+
+// Programs are loaded at PROGRAM_START, so that is the most RAM they can fill.
+const MAX_ROM_SIZE: usize = XOCHIP_MEMORY - PROGRAM_START;
+
 pub struct CartridgeModule {
-    pub rom: [u8; 3584],
+    pub rom: Vec<u8>,
     pub size: usize,
 }
 
 impl CartridgeModule {
     pub fn new(filename: &str) -> Self {
-        let mut f = File::open(filename).expect("file not found");
-        let mut buffer = [0u8; 3584];
+        let f = File::open(filename).expect("file not found");
+        let mut buffer = Vec::new();
 
-        let bytes_read = if let Ok(bytes_read) = f.read(&mut buffer) {
-            bytes_read
-        } else {
-            0
-        };
+        let bytes_read = f
+            .take(MAX_ROM_SIZE as u64)
+            .read_to_end(&mut buffer)
+            .unwrap_or_default();
 
         CartridgeModule {
             rom: buffer,
             size: bytes_read,
         }
     }
+
+    pub fn hash(&self) -> u64 {
+        rom_hash(&self.rom)
+    }
+}
+
+/// Identifies a ROM by its contents (64-bit FNV-1a), so files can be renamed.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
 }
@@ -1,52 +1,275 @@
 use sdl2;
-use sdl2::pixels;
+use sdl2::hint;
+use sdl2::pixels::{self, PixelFormatEnum};
+#[cfg(any(test, feature = "bench"))]
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
+use sdl2::render::{Canvas, RenderTarget, Texture};
 use sdl2::video::Window;
 
+use crt::{Crt, CrtRenderer};
+use display::{DisplayOptions, Palette, Rgb};
+use flicker::{AntiFlicker, FrameFilter};
+use Vram;
 use CHIP8_HEIGHT;
 use CHIP8_WIDTH;
 
-const SCALE: u32 = 20;
-const WIDTH: u32 = CHIP8_WIDTH as u32 * SCALE;
-const HEIGHT: u32 = CHIP8_HEIGHT as u32 * SCALE;
+// Largest window pixels per CHIP-8 pixel the CRT effects are rendered at;
+// past this SDL scales the result up, which keeps the CPU cost bounded.
+const MAX_CRT_SCALE: usize = 8;
+
+// Shift+G and Ctrl+G change the CRT effects' strength in these steps, as a
+// percentage of the configured strengths, up to the maximum.
+const CRT_STEP: u32 = 25;
+const MAX_CRT_STRENGTH: u32 = 300;
+
+// Pixels uploaded to one streaming texture, which SDL stretches over the
+// canvas, instead of being drawn a rectangle at a time. Private, since it must
+// always draw to the canvas that created its texture: each one here belongs
+// to a single canvas it's kept next to.
+#[derive(Default)]
+struct FrameTexture {
+    texture: Option<(Texture, (u32, u32))>,
+}
+
+impl FrameTexture {
+    fn new() -> Self {
+        FrameTexture::default()
+    }
+
+    // Draws width x height RGB24 pixels, row by row, over the whole of the
+    // canvas's logical size.
+    fn draw<T: RenderTarget>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        rgb: &[u8],
+        width: u32,
+        height: u32,
+    ) -> Result<(), String> {
+        if self.texture.as_ref().map(|&(_, size)| size) != Some((width, height)) {
+            if let Some((texture, _)) = self.texture.take() {
+                // SAFETY: the canvas that created the texture is still alive.
+                unsafe { texture.destroy() };
+            }
+            let texture = canvas
+                .create_texture_streaming(PixelFormatEnum::RGB24, width, height)
+                .map_err(|error| error.to_string())?;
+            self.texture = Some((texture, (width, height)));
+        }
+        if let Some((ref mut texture, _)) = self.texture {
+            texture
+                .update(None, rgb, width as usize * 3)
+                .map_err(|error| error.to_string())?;
+            canvas.copy(texture, None, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// A canvas and a texture to draw frames through the way `DisplayModule`
+/// does, for benchmarking against `draw_rects`. Owning both keeps the texture
+/// with the canvas that created it.
+#[cfg(feature = "bench")]
+pub struct TextureCanvas<T: RenderTarget> {
+    canvas: Canvas<T>,
+    frame: FrameTexture,
+}
+
+#[cfg(feature = "bench")]
+impl<T: RenderTarget> TextureCanvas<T> {
+    pub fn new(canvas: Canvas<T>) -> Self {
+        TextureCanvas {
+            canvas,
+            frame: FrameTexture::new(),
+        }
+    }
+
+    pub fn canvas(&mut self) -> &mut Canvas<T> {
+        &mut self.canvas
+    }
+
+    /// Draws width x height RGB24 pixels over the canvas's logical size.
+    pub fn draw(&mut self, rgb: &[u8], width: u32, height: u32) -> Result<(), String> {
+        self.frame.draw(&mut self.canvas, rgb, width, height)
+    }
+}
+
+/// The renderer `FrameTexture` replaced: one filled rectangle per pixel. Kept
+/// as the reference the texture renderer is tested and benchmarked against.
+#[cfg(any(test, feature = "bench"))]
+pub fn draw_rects<T: RenderTarget>(canvas: &mut Canvas<T>, colors: &[Rgb], width: usize) {
+    for (index, &[r, g, b]) in colors.iter().enumerate() {
+        let (x, y) = (index % width, index / width);
+        canvas.set_draw_color(pixels::Color::RGB(r, g, b));
+        let _ = canvas.fill_rect(Rect::new(x as i32, y as i32, 1, 1));
+    }
+}
+
+/// A frame using all four colours of an XO-CHIP palette in an irregular
+/// pattern, for comparing renderers.
+#[cfg(any(test, feature = "bench"))]
+pub fn sample_frame(width: usize, height: usize) -> Vec<Rgb> {
+    let palette = ::display::palette("octo").unwrap();
+    (0..width * height)
+        .map(|index| palette[(index * 7 + index / width * 3) % 5 % 4])
+        .collect()
+}
 
 pub struct DisplayModule {
     canvas: Canvas<Window>,
+    palette: Palette,
+    filter: FrameFilter,
+    // What the next present shows, and its resolution.
+    colors: Vec<Rgb>,
+    size: (usize, usize),
+    frame: FrameTexture,
+    crt: CrtRenderer,
+    // The effects G toggles back on, and the percentage of them shown.
+    crt_on: Crt,
+    crt_strength: u32,
+    // The frame after the CRT effects, larger than the plain one.
+    crt_frame: FrameTexture,
+    crt_pixels: Vec<u8>,
 }
 
 impl DisplayModule {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, options: &DisplayOptions) -> Self {
         let video_subsystem = sdl_context.video().unwrap();
-        let window = video_subsystem
-            .window("CHIP-8", WIDTH, HEIGHT)
-            .position_centered()
-            .opengl()
-            .build()
-            .unwrap();
-        let mut canvas = window.into_canvas().build().unwrap();
-        DisplayModule { canvas: canvas }
-    }
-
-    pub fn draw(&mut self, pixels: &[[u8; CHIP8_WIDTH]; CHIP8_HEIGHT]) {
-        for (y, row) in pixels.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate() {
-                let x = x as u32 * SCALE;
-                let y = y as u32 * SCALE;
-
-                self.canvas.set_draw_color(color(col));
-                let _ = self
-                    .canvas
-                    .fill_rect(Rect::new(x as i32, y as i32, SCALE, SCALE));
+        // Nearest-neighbour scaling, so textures stretched over the window
+        // keep hard pixel edges.
+        hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+        let mut window = video_subsystem.window(
+            "CHIP-8",
+            CHIP8_WIDTH as u32 * options.scale,
+            CHIP8_HEIGHT as u32 * options.scale,
+        );
+        window.position_centered().opengl().resizable();
+        if options.fullscreen {
+            window.fullscreen_desktop();
+        }
+        let mut canvas = window.build().unwrap().into_canvas();
+        if options.vsync {
+            canvas = canvas.present_vsync();
+        }
+        DisplayModule {
+            canvas: canvas.build().unwrap(),
+            palette: options.palette,
+            filter: FrameFilter::new(options.anti_flicker),
+            colors: Vec::new(),
+            size: (0, 0),
+            frame: FrameTexture::new(),
+            crt: CrtRenderer::new(options.crt),
+            crt_on: if options.crt.is_off() {
+                Crt::CLASSIC
+            } else {
+                options.crt
+            },
+            crt_strength: 100,
+            crt_frame: FrameTexture::new(),
+            crt_pixels: Vec::new(),
+        }
+    }
+
+    /// Turns the CRT effects off, or back on with the configured settings
+    /// (or `Crt::CLASSIC` if none were) at the current strength. Returns
+    /// whether they are now on.
+    pub fn toggle_crt(&mut self) -> bool {
+        let crt = if self.crt.crt().is_off() {
+            self.crt_on.scaled(self.crt_strength)
+        } else {
+            Crt::default()
+        };
+        self.crt.set_crt(crt);
+        !crt.is_off()
+    }
+
+    /// Makes the CRT effects one step stronger or weaker, turning them on if
+    /// they were off. Returns the new strength as a percentage of the
+    /// configured one.
+    pub fn adjust_crt(&mut self, stronger: bool) -> u32 {
+        self.crt_strength = if stronger {
+            (self.crt_strength + CRT_STEP).min(MAX_CRT_STRENGTH)
+        } else {
+            self.crt_strength.saturating_sub(CRT_STEP).max(CRT_STEP)
+        };
+        self.crt.set_crt(self.crt_on.scaled(self.crt_strength));
+        self.crt_strength
+    }
+
+    /// Whether frames need passing to `update` even when nothing was drawn,
+    /// because the anti-flicker mode fades or blends them over time.
+    pub fn filtering(&self) -> bool {
+        self.filter.mode() != AntiFlicker::Off
+    }
+
+    /// Takes the top-left width x height corner of pixels as the newest
+    /// frame, without showing it yet.
+    pub fn update(&mut self, pixels: &Vram, width: usize, height: usize) {
+        self.filter.push(pixels, width, height);
+        self.colors = self.filter.colors(&self.palette);
+        if self.size != (width, height) {
+            // SDL scales this to the window, so both the 64x32 and 128x64
+            // resolutions fill it, and letterboxes any window that isn't 2:1.
+            let _ = self.canvas.set_logical_size(width as u32, height as u32);
+            self.size = (width, height);
+        }
+    }
+
+    /// Shows the newest frame.
+    pub fn present(&mut self) {
+        self.canvas.set_draw_color(pixels::Color::BLACK);
+        self.canvas.clear();
+        let (width, height) = self.size;
+        if width > 0 && height > 0 {
+            if self.crt.crt().is_off() {
+                if let Err(error) = self.frame.draw(
+                    &mut self.canvas,
+                    self.colors.as_flattened(),
+                    width as u32,
+                    height as u32,
+                ) {
+                    eprintln!("could not draw the frame: {}", error);
+                }
+            } else if let Err(error) = self.present_crt() {
+                eprintln!("could not draw CRT effects: {}", error);
+                self.crt.set_crt(Crt::default());
             }
         }
         self.canvas.present();
     }
-}
-fn color(value: u8) -> pixels::Color {
-    if value == 0 {
-        pixels::Color::RGB(0, 0, 0)
-    } else {
-        pixels::Color::RGB(255, 255, 255)
+
+    // Renders the frame through the CRT effects at about the window's
+    // resolution and stretches it over the logical size.
+    fn present_crt(&mut self) -> Result<(), String> {
+        let (width, height) = self.size;
+        let (window_width, window_height) = self.canvas.output_size()?;
+        let scale = (window_width as usize / width)
+            .min(window_height as usize / height)
+            .clamp(2, MAX_CRT_SCALE);
+        let (crt_width, crt_height) = (width * scale, height * scale);
+        self.crt_pixels.resize(crt_width * crt_height * 3, 0);
+        self.crt.render(
+            &self.colors,
+            self.palette[0],
+            width,
+            height,
+            scale,
+            &mut self.crt_pixels,
+        );
+        self.crt_frame.draw(
+            &mut self.canvas,
+            &self.crt_pixels,
+            crt_width as u32,
+            crt_height as u32,
+        )
+    }
+
+    /// Updates and presents at once.
+    pub fn draw(&mut self, pixels: &Vram, width: usize, height: usize) {
+        self.update(pixels, width, height);
+        self.present();
     }
 }
+
+#[cfg(test)]
+#[path = "./display_mod_test.rs"]
+mod display_mod_test;
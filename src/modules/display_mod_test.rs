@@ -0,0 +1,52 @@
+use super::*;
+use sdl2::pixels::Color;
+use sdl2::surface::Surface;
+use {SCHIP_HEIGHT, SCHIP_WIDTH};
+
+// Draws on SDL's software renderer, so no window is needed.
+fn render<F>(width: usize, height: usize, scale: u32, draw: F) -> Vec<u8>
+where
+    F: FnOnce(&mut Canvas<Surface<'static>>),
+{
+    let surface = Surface::new(
+        width as u32 * scale,
+        height as u32 * scale,
+        PixelFormatEnum::RGB24,
+    )
+    .unwrap();
+    let mut canvas = surface.into_canvas().unwrap();
+    canvas
+        .set_logical_size(width as u32, height as u32)
+        .unwrap();
+    canvas.set_draw_color(Color::BLACK);
+    canvas.clear();
+    draw(&mut canvas);
+    canvas.read_pixels(None, PixelFormatEnum::RGB24).unwrap()
+}
+
+// The texture renderer draws exactly what the old rectangle-per-pixel one
+// did, at every `--scale` and both resolutions.
+#[test]
+fn test_texture_matches_rects() {
+    hint::set("SDL_RENDER_SCALE_QUALITY", "0");
+    for &(width, height) in &[(CHIP8_WIDTH, CHIP8_HEIGHT), (SCHIP_WIDTH, SCHIP_HEIGHT)] {
+        let colors = sample_frame(width, height);
+        for scale in 1..=20 {
+            let rects = render(width, height, scale, |canvas| {
+                draw_rects(canvas, &colors, width)
+            });
+            let texture = render(width, height, scale, |canvas| {
+                FrameTexture::new()
+                    .draw(canvas, colors.as_flattened(), width as u32, height as u32)
+                    .unwrap()
+            });
+            assert!(
+                rects == texture,
+                "{}x{} frame differs at scale {}",
+                width,
+                height,
+                scale
+            );
+        }
+    }
+}
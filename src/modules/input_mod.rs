@@ -1,23 +1,225 @@
 use sdl2;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::GameControllerSubsystem;
+
+use keymap::{KeyMap, PAD_PREFIX};
+
+/// Front-end actions triggered by keys outside the hex keypad.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    /// Escape, or closing the window.
+    Quit,
+    /// P pauses and resumes.
+    Pause,
+    /// N runs a single frame, pausing first if need be.
+    FrameAdvance,
+    /// Tab toggles running faster than real time.
+    FastForward,
+    /// F11 restarts the program without reloading it.
+    SoftReset,
+    /// Shift+F11 starts over with a fresh processor and the cartridge
+    /// reloaded.
+    HardReset,
+    /// F12 saves the screen as a PNG, at the window's scale and in its
+    /// palette.
+    Screenshot,
+    /// Shift+F12 starts and stops recording an animated GIF.
+    ToggleGif,
+    /// Ctrl+F12 starts and stops recording the sound to a WAV file.
+    ToggleWav,
+    /// G turns the CRT effects on and off.
+    ToggleCrt,
+    /// Shift+G makes the CRT effects stronger.
+    CrtStronger,
+    /// Ctrl+G makes them weaker.
+    CrtWeaker,
+    /// =
+    VolumeUp,
+    /// -
+    VolumeDown,
+    /// M mutes and unmutes.
+    Mute,
+    /// T switches to the next waveform.
+    Waveform,
+    /// ] raises the tone a semitone.
+    ToneUp,
+    /// [ lowers it.
+    ToneDown,
+    /// Shift+F1..F9
+    SaveState(u8),
+    /// F1..F9
+    LoadState(u8),
+    /// F10 rebinds the keypad for this ROM, Shift+F10 for every ROM. The
+    /// next 16 key presses become keypad keys 0 to F.
+    Rebind { all_roms: bool },
+    /// The host key or controller button pressed for a keypad key while
+    /// rebinding, by `KeyMap` name, or `None` if it was skipped with Escape.
+    Bind(usize, Option<String>),
+}
+
+/// What the front end should act on after a poll.
+pub struct Input {
+    pub keypad: [bool; 16],
+    /// Hotkeys pressed since the last poll, oldest first.
+    pub hotkeys: Vec<Hotkey>,
+    /// The rewind key is held.
+    pub rewinding: bool,
+    /// The window was resized or uncovered and needs drawing again.
+    pub redraw: bool,
+    /// Names of the controllers plugged in since the last poll.
+    pub connected: Vec<String>,
+}
+
+// Held, rather than pressed, to run the emulation backwards.
+const REWIND_KEY: Keycode = Keycode::Backspace;
+const REBIND_KEY: Keycode = Keycode::F10;
+const SKIP_KEY: Keycode = Keycode::Escape;
+
+// A controller input that can be bound to a keypad key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PadInput {
+    Button(Button),
+    /// An axis pushed past the stick threshold, in the positive direction if
+    /// true.
+    Axis(Axis, bool),
+}
+
+impl PadInput {
+    // `a`, `dpup`, `leftx-`, ... as SDL names them, without the prefix.
+    fn from_name(name: &str) -> Option<PadInput> {
+        if let Some(axis) = name.strip_suffix('+') {
+            Axis::from_string(axis).map(|axis| PadInput::Axis(axis, true))
+        } else if let Some(axis) = name.strip_suffix('-') {
+            Axis::from_string(axis).map(|axis| PadInput::Axis(axis, false))
+        } else {
+            Button::from_string(name).map(PadInput::Button)
+        }
+    }
+
+    fn pressed(self, controller: &GameController, threshold: i32) -> bool {
+        match self {
+            PadInput::Button(button) => controller.button(button),
+            PadInput::Axis(axis, true) => controller.axis(axis) as i32 >= threshold,
+            PadInput::Axis(axis, false) => controller.axis(axis) as i32 <= -threshold,
+        }
+    }
+}
 
 pub struct InputModule {
     events: sdl2::EventPump,
+    bindings: Vec<(Keycode, usize)>,
+    // The keypad key waiting for a host key, while rebinding.
+    rebinding: Option<usize>,
+    // None if SDL couldn't start its controller support; the keyboard still
+    // works.
+    controller_subsystem: Option<GameControllerSubsystem>,
+    controllers: Vec<GameController>,
+    pad_bindings: Vec<(PadInput, usize)>,
+    // Axis value that counts as pushed.
+    stick_threshold: i32,
 }
 
 impl InputModule {
     pub fn new(sdl2_context: &sdl2::Sdl) -> Self {
-        InputModule {
+        // Controllers already plugged in arrive as ControllerDeviceAdded
+        // events, just like ones plugged in later.
+        let controller_subsystem = sdl2_context
+            .game_controller()
+            .map_err(|error| eprintln!("game controllers unavailable: {}", error))
+            .ok();
+        let mut input = InputModule {
             events: sdl2_context.event_pump().unwrap(),
+            bindings: Vec::new(),
+            rebinding: None,
+            controller_subsystem,
+            controllers: Vec::new(),
+            pad_bindings: Vec::new(),
+            stick_threshold: 0,
+        };
+        input.set_keymap(&KeyMap::default());
+        input
+    }
+
+    /// Switches to new key and controller bindings. Returns the names SDL
+    /// doesn't recognise, which are left unbound.
+    pub fn set_keymap(&mut self, keymap: &KeyMap) -> Vec<String> {
+        let mut unknown = Vec::new();
+        self.bindings.clear();
+        self.pad_bindings.clear();
+        for keypad in 0..16 {
+            for name in keymap.host_keys(keypad) {
+                let pad_name = name
+                    .get(..PAD_PREFIX.len())
+                    .filter(|prefix| prefix.eq_ignore_ascii_case(PAD_PREFIX))
+                    .map(|_| &name[PAD_PREFIX.len()..]);
+                match pad_name {
+                    Some(pad_name) => match PadInput::from_name(pad_name) {
+                        Some(input) => self.pad_bindings.push((input, keypad)),
+                        None => unknown.push(name.clone()),
+                    },
+                    None => match Keycode::from_name(name) {
+                        Some(key) => self.bindings.push((key, keypad)),
+                        None => unknown.push(name.clone()),
+                    },
+                }
+            }
         }
+        self.stick_threshold = i16::MAX as i32 * keymap.stick_threshold() as i32 / 100;
+        unknown
     }
 
-    pub fn poll(&mut self) -> Result<[bool; 16], ()> {
-        for event in self.events.poll_iter() {
-            if let Event::Quit { .. } = event {
-                return Err(());
-            };
+    /// Handles pending events and reads the keypad. Keys bound to the keypad
+    /// don't also trigger hotkeys. While rebinding, no keypad keys are down.
+    pub fn poll(&mut self) -> Input {
+        let mut hotkeys = Vec::new();
+        let mut redraw = false;
+        let mut connected = Vec::new();
+        let events: Vec<Event> = self.events.poll_iter().collect();
+        for event in events {
+            match event {
+                Event::Quit { .. } => hotkeys.push(Hotkey::Quit),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..) | WindowEvent::Exposed,
+                    ..
+                } => redraw = true,
+                Event::ControllerDeviceAdded { which, .. } => {
+                    connected.extend(self.connect(which));
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    self.controllers
+                        .retain(|controller| controller.instance_id() != which);
+                }
+                Event::ControllerButtonDown { button, .. } if self.rebinding.is_some() => {
+                    let host = format!("{}{}", PAD_PREFIX, button.string());
+                    hotkeys.push(self.bind_next(Some(host)));
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    let ctrl = keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+                    if self.rebinding.is_some() {
+                        hotkeys.push(self.bind_next(if key == SKIP_KEY {
+                            None
+                        } else {
+                            Some(key.name())
+                        }));
+                    } else if self.is_bound(key) {
+                        // Bound keys only press their keypad key.
+                    } else if key == REBIND_KEY {
+                        self.rebinding = Some(0);
+                        hotkeys.push(Hotkey::Rebind { all_roms: shift });
+                    } else if let Some(hotkey) = hotkey(key, shift, ctrl) {
+                        hotkeys.push(hotkey);
+                    }
+                }
+                _ => {}
+            }
         }
         let keys: Vec<Keycode> = self
             .events
@@ -25,35 +227,115 @@ impl InputModule {
             .pressed_scancodes()
             .filter_map(Keycode::from_scancode)
             .collect();
+        let rewinding = keys.contains(&REWIND_KEY) && !self.is_bound(REWIND_KEY);
 
         let mut chip8_keys = [false; 16];
+        if self.rebinding.is_some() {
+            return Input {
+                keypad: chip8_keys,
+                hotkeys,
+                rewinding,
+                redraw,
+                connected,
+            };
+        }
 
         for key in keys {
-            let index = match key {
-                Keycode::Num1 => Some(0x1),
-                Keycode::Num2 => Some(0x2),
-                Keycode::Num3 => Some(0x3),
-                Keycode::Num4 => Some(0xc),
-                Keycode::Q => Some(0x4),
-                Keycode::W => Some(0x5),
-                Keycode::E => Some(0x6),
-                Keycode::R => Some(0xd),
-                Keycode::A => Some(0x7),
-                Keycode::S => Some(0x8),
-                Keycode::D => Some(0x9),
-                Keycode::F => Some(0xe),
-                Keycode::Z => Some(0xa),
-                Keycode::X => Some(0x0),
-                Keycode::C => Some(0xb),
-                Keycode::V => Some(0xf),
-                _ => None,
-            };
+            for &(bound, keypad) in self.bindings.iter() {
+                if bound == key {
+                    chip8_keys[keypad] = true;
+                }
+            }
+        }
+        for controller in self.controllers.iter() {
+            for &(input, keypad) in self.pad_bindings.iter() {
+                if input.pressed(controller, self.stick_threshold) {
+                    chip8_keys[keypad] = true;
+                }
+            }
+        }
+
+        Input {
+            keypad: chip8_keys,
+            hotkeys,
+            rewinding,
+            redraw,
+            connected,
+        }
+    }
+
+    fn is_bound(&self, key: Keycode) -> bool {
+        self.bindings.iter().any(|&(bound, _)| bound == key)
+    }
+
+    // Binds the keypad key being rebound and moves on to the next. Only
+    // called while rebinding.
+    fn bind_next(&mut self, host: Option<String>) -> Hotkey {
+        let keypad = self.rebinding.unwrap_or(0);
+        self.rebinding = if keypad < 0xF { Some(keypad + 1) } else { None };
+        Hotkey::Bind(keypad, host)
+    }
+
+    // Opens a newly added controller and returns its name.
+    fn connect(&mut self, joystick_index: u32) -> Option<String> {
+        let subsystem = self.controller_subsystem.as_ref()?;
+        match subsystem.open(joystick_index) {
+            Ok(controller) => {
+                let name = controller.name();
+                self.controllers.push(controller);
+                Some(name)
+            }
+            Err(error) => {
+                eprintln!("could not open controller: {}", error);
+                None
+            }
+        }
+    }
+}
 
-            if let Some(i) = index {
-                chip8_keys[i] = true;
+fn hotkey(key: Keycode, shift: bool, ctrl: bool) -> Option<Hotkey> {
+    let hotkey = match key {
+        Keycode::Escape => Hotkey::Quit,
+        Keycode::P => Hotkey::Pause,
+        Keycode::N => Hotkey::FrameAdvance,
+        Keycode::Tab => Hotkey::FastForward,
+        Keycode::F11 if shift => Hotkey::HardReset,
+        Keycode::F11 => Hotkey::SoftReset,
+        Keycode::F12 if ctrl => Hotkey::ToggleWav,
+        Keycode::F12 if shift => Hotkey::ToggleGif,
+        Keycode::F12 => Hotkey::Screenshot,
+        Keycode::G if shift => Hotkey::CrtStronger,
+        Keycode::G if ctrl => Hotkey::CrtWeaker,
+        Keycode::G => Hotkey::ToggleCrt,
+        Keycode::Equals => Hotkey::VolumeUp,
+        Keycode::Minus => Hotkey::VolumeDown,
+        Keycode::M => Hotkey::Mute,
+        Keycode::T => Hotkey::Waveform,
+        Keycode::RightBracket => Hotkey::ToneUp,
+        Keycode::LeftBracket => Hotkey::ToneDown,
+        _ => {
+            let slot = function_key(key)?;
+            if shift {
+                Hotkey::SaveState(slot)
+            } else {
+                Hotkey::LoadState(slot)
             }
         }
+    };
+    Some(hotkey)
+}
 
-        Ok(chip8_keys)
+fn function_key(key: Keycode) -> Option<u8> {
+    match key {
+        Keycode::F1 => Some(1),
+        Keycode::F2 => Some(2),
+        Keycode::F3 => Some(3),
+        Keycode::F4 => Some(4),
+        Keycode::F5 => Some(5),
+        Keycode::F6 => Some(6),
+        Keycode::F7 => Some(7),
+        Keycode::F8 => Some(8),
+        Keycode::F9 => Some(9),
+        _ => None,
     }
 }
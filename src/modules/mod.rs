@@ -1,9 +1,17 @@
 mod cart_mod;
+#[cfg(feature = "sdl")]
 mod display_mod;
+#[cfg(feature = "sdl")]
 mod input_mod;
+#[cfg(feature = "sdl")]
 mod sound_mod;
 
-pub use self::cart_mod::CartridgeModule;
+pub use self::cart_mod::{rom_hash, CartridgeModule};
+#[cfg(feature = "sdl")]
 pub use self::display_mod::DisplayModule;
-pub use self::input_mod::InputModule;
+#[cfg(feature = "bench")]
+pub use self::display_mod::{draw_rects, sample_frame, TextureCanvas};
+#[cfg(feature = "sdl")]
+pub use self::input_mod::{Hotkey, Input, InputModule};
+#[cfg(feature = "sdl")]
 pub use self::sound_mod::SoundModule;
@@ -1,56 +1,115 @@
 use sdl2;
 use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
 
+use processor::{SoundSpan, DEFAULT_AUDIO_PATTERN, DEFAULT_PITCH};
+use sound::{Beeper, SoundOptions, SAMPLE_RATE};
+
 pub struct SoundModule {
-    device: AudioDevice<SquareWave>,
+    device: AudioDevice<BeeperCallback>,
+    options: SoundOptions,
+    pattern: [u8; 16],
+    pitch: u8,
+    sample_rate: u32,
 }
 
 impl SoundModule {
-    pub fn new(sdl_context: &sdl2::Sdl) -> Self {
+    pub fn new(sdl_context: &sdl2::Sdl, options: SoundOptions) -> Self {
         let sound_subsystem = sdl_context.audio().unwrap();
 
         let desired_spec = AudioSpecDesired {
-            freq: Some(44100),
+            freq: Some(SAMPLE_RATE as i32),
             channels: Some(1),
-            samples: None,
+            // Small buffers, so each callback needs well under the beeper's
+            // latency of queued timeline.
+            samples: Some(512),
         };
 
         let device = sound_subsystem
-            .open_playback(None, &desired_spec, |spec| {
-                println!("{:?}", spec);
-
-                SquareWave {
-                    phase_inc: 240.0 / spec.freq as f32,
-                    phase: 0.0,
-                    volume: 0.25,
-                }
+            .open_playback(None, &desired_spec, |spec| BeeperCallback {
+                beeper: Beeper::new(options, spec.freq as u32),
+                capture: None,
             })
             .unwrap();
+        let sample_rate = device.spec().freq as u32;
+        // The device plays continuously, silence included, so beeps start
+        // and stop on the right sample rather than with the device.
+        device.resume();
+
+        SoundModule {
+            device,
+            options,
+            pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            sample_rate,
+        }
+    }
+
+    pub fn options(&self) -> SoundOptions {
+        self.options
+    }
+
+    /// Changes the tone, volume or mute while playing. Out of range volumes
+    /// and frequencies are clamped.
+    pub fn set_options(&mut self, options: SoundOptions) {
+        let mut beeper = self.device.lock();
+        beeper.beeper.set_options(options);
+        self.options = beeper.beeper.options();
+    }
 
-        SoundModule { device: device }
+    // Hands a new XO-CHIP pattern and pitch to the audio thread.
+    pub fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        if *pattern == self.pattern && pitch == self.pitch {
+            return;
+        }
+        self.pattern = *pattern;
+        self.pitch = pitch;
+        self.device.lock().beeper.set_pattern(pattern, pitch);
+    }
+
+    /// Queues the sound timer's timeline for the frame just run, to be
+    /// played over this many seconds.
+    pub fn push(&mut self, span: &SoundSpan, seconds: f32) {
+        self.device.lock().beeper.push(span, seconds);
+    }
+
+    /// The rate the device actually plays at, which captures are in.
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Starts keeping a copy of everything played, discarding any earlier
+    /// capture.
+    pub fn start_capture(&mut self) {
+        self.device.lock().capture = Some(Vec::new());
     }
 
-    pub fn start_beep(&self) {
-        self.device.resume();
+    /// Stops capturing and hands back the samples played since the start.
+    pub fn stop_capture(&mut self) -> Vec<f32> {
+        self.device.lock().capture.take().unwrap_or_default()
     }
-    pub fn stop_beep(&self) {
-        self.device.pause();
+
+    /// The samples captured since the last call, leaving the capture running.
+    pub fn take_capture(&mut self) -> Vec<f32> {
+        self.device
+            .lock()
+            .capture
+            .as_mut()
+            .map_or_else(Vec::new, |capture| capture.split_off(0))
     }
 }
 
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32,
+struct BeeperCallback {
+    beeper: Beeper,
+    capture: Option<Vec<f32>>,
 }
 
-impl AudioCallback for SquareWave {
+impl AudioCallback for BeeperCallback {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        for x in out.iter_mut() {
-            *x = self.volume * if self.phase < 0.5 { 1.0 } else { -1.0 };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
+        self.beeper.fill(out);
+        if let Some(ref mut capture) = self.capture {
+            capture.extend_from_slice(out);
         }
     }
 }
@@ -0,0 +1,257 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clock::Clock;
+use modules::rom_hash;
+use processor::Processor;
+use quirks::{IndexIncrement, Quirks};
+
+pub const HEADER: &str = "CHIP8-MOVIE";
+// Version 2 changed the seeded generator, so older movies would desync.
+pub const VERSION: u32 = 2;
+/// The longest movie `parse` accepts, in frames: ten hours at 60 Hz. Longer
+/// is a corrupt file rather than a recording, and would exhaust memory.
+pub const MAX_FRAMES: usize = 10 * 60 * 60 * 60;
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    /// The file doesn't start with the movie header.
+    NotAMovie,
+    /// The movie was written by an older or newer, incompatible version.
+    UnsupportedVersion(u32),
+    /// A line, numbered from 1, that can't be parsed.
+    Invalid(usize),
+    /// The movie was recorded with a different ROM.
+    WrongRom,
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MovieError::Io(ref error) => write!(f, "{}", error),
+            MovieError::NotAMovie => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => {
+                write!(f, "unsupported movie version {}", version)
+            }
+            MovieError::Invalid(line) => write!(f, "movie has an invalid line {}", line),
+            MovieError::WrongRom => write!(f, "movie was recorded with a different ROM"),
+        }
+    }
+}
+
+impl Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(error: io::Error) -> Self {
+        MovieError::Io(error)
+    }
+}
+
+/// A recorded session: everything needed to replay it exactly, plus the
+/// keypad state of every frame.
+///
+/// Movies are text, so they can be attached to bug reports and diffed:
+///
+/// ```text
+/// CHIP8-MOVIE 2
+/// rom 8c3b0d5f21a9e4c7
+/// seed 12345
+/// ips 700
+/// quirks vf_reset=1 shift_uses_vy=1 index_increment=x+1 jump_uses_vx=0 clip_sprites=1
+/// 0000 120
+/// 0020 6
+/// ```
+///
+/// Each keypad line is a bitmask of the held keys (bit n for key n) and how
+/// many frames in a row it was held.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub rom_hash: u64,
+    pub seed: u64,
+    pub instructions_per_second: u32,
+    pub quirks: Quirks,
+    frames: Vec<[bool; 16]>,
+}
+
+impl Movie {
+    /// An empty movie, ready to record.
+    pub fn new(rom: &[u8], seed: u64, instructions_per_second: u32, quirks: Quirks) -> Self {
+        Movie {
+            rom_hash: rom_hash(rom),
+            seed,
+            instructions_per_second,
+            quirks,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Appends the keypad for the next frame.
+    pub fn record(&mut self, keypad: [bool; 16]) {
+        self.frames.push(keypad);
+    }
+
+    /// Number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The keypad during frame, counting from 0, or `None` past the end.
+    pub fn keypad(&self, frame: u64) -> Option<[bool; 16]> {
+        self.frames.get(frame as usize).cloned()
+    }
+
+    /// A processor and clock in the state the recording started from, with
+    /// rom loaded.
+    pub fn start(&self, rom: &[u8]) -> Result<(Processor, Clock), MovieError> {
+        if rom_hash(rom) != self.rom_hash {
+            return Err(MovieError::WrongRom);
+        }
+        let mut processor = Processor::new(self.quirks);
+        processor.seed(self.seed);
+        processor.load(rom);
+        Ok((processor, Clock::new(self.instructions_per_second)))
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Movie, MovieError> {
+        Movie::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> Result<Movie, MovieError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line));
+
+        match lines.next().and_then(|(_, line)| line.split_once(' ')) {
+            Some((HEADER, version)) => match version.parse() {
+                Ok(VERSION) => {}
+                Ok(version) => return Err(MovieError::UnsupportedVersion(version)),
+                Err(_) => return Err(MovieError::Invalid(1)),
+            },
+            _ => return Err(MovieError::NotAMovie),
+        }
+
+        let mut field = |name: &str| match lines.next() {
+            Some((number, line)) => match line.split_once(' ') {
+                Some((key, value)) if key == name => Ok((number, value)),
+                _ => Err(MovieError::Invalid(number)),
+            },
+            None => Err(MovieError::NotAMovie),
+        };
+        let (number, rom) = field("rom")?;
+        let rom_hash = u64::from_str_radix(rom, 16).map_err(|_| MovieError::Invalid(number))?;
+        let (number, seed) = field("seed")?;
+        let seed = seed.parse().map_err(|_| MovieError::Invalid(number))?;
+        let (number, ips) = field("ips")?;
+        let instructions_per_second = ips.parse().map_err(|_| MovieError::Invalid(number))?;
+        let (number, quirks) = field("quirks")?;
+        let quirks = parse_quirks(quirks).ok_or(MovieError::Invalid(number))?;
+
+        let mut frames = Vec::new();
+        for (number, line) in lines {
+            let (mask, count) = line.split_once(' ').unwrap_or((line, "1"));
+            let mask = u16::from_str_radix(mask, 16).map_err(|_| MovieError::Invalid(number))?;
+            let count = count
+                .parse::<usize>()
+                .ok()
+                .filter(|&count| count <= MAX_FRAMES - frames.len())
+                .ok_or(MovieError::Invalid(number))?;
+            let mut keypad = [false; 16];
+            for (key, pressed) in keypad.iter_mut().enumerate() {
+                *pressed = mask & (1 << key) != 0;
+            }
+            frames.extend(std::iter::repeat_n(keypad, count));
+        }
+
+        Ok(Movie {
+            rom_hash,
+            seed,
+            instructions_per_second,
+            quirks,
+            frames,
+        })
+    }
+}
+
+impl fmt::Display for Movie {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} {}", HEADER, VERSION)?;
+        writeln!(f, "rom {:016x}", self.rom_hash)?;
+        writeln!(f, "seed {}", self.seed)?;
+        writeln!(f, "ips {}", self.instructions_per_second)?;
+        writeln!(f, "quirks {}", format_quirks(self.quirks))?;
+
+        // Run-length encode, since keys are held for many frames at a time.
+        let mut frames = self.frames.iter().peekable();
+        while let Some(keypad) = frames.next() {
+            let mut count = 1;
+            while frames.peek() == Some(&keypad) {
+                frames.next();
+                count += 1;
+            }
+            let mask = keypad
+                .iter()
+                .enumerate()
+                .fold(0u16, |mask, (key, &pressed)| mask | (pressed as u16) << key);
+            writeln!(f, "{:04x} {}", mask, count)?;
+        }
+        Ok(())
+    }
+}
+
+fn format_quirks(quirks: Quirks) -> String {
+    format!(
+        "vf_reset={} shift_uses_vy={} index_increment={} jump_uses_vx={} clip_sprites={}",
+        quirks.vf_reset as u8,
+        quirks.shift_uses_vy as u8,
+        match quirks.index_increment {
+            IndexIncrement::Unchanged => "0",
+            IndexIncrement::ByX => "x",
+            IndexIncrement::ByXPlusOne => "x+1",
+        },
+        quirks.jump_uses_vx as u8,
+        quirks.clip_sprites as u8
+    )
+}
+
+fn parse_quirks(text: &str) -> Option<Quirks> {
+    let fields: Vec<Option<(&str, &str)>> =
+        text.split(' ').map(|field| field.split_once('=')).collect();
+    let flag = |index: usize, name: &str| match fields.get(index)? {
+        Some((key, "0")) if *key == name => Some(false),
+        Some((key, "1")) if *key == name => Some(true),
+        _ => None,
+    };
+    let index_increment = match fields.get(2)? {
+        Some(("index_increment", "0")) => IndexIncrement::Unchanged,
+        Some(("index_increment", "x")) => IndexIncrement::ByX,
+        Some(("index_increment", "x+1")) => IndexIncrement::ByXPlusOne,
+        _ => return None,
+    };
+    if fields.len() != 5 {
+        return None;
+    }
+    Some(Quirks {
+        vf_reset: flag(0, "vf_reset")?,
+        shift_uses_vy: flag(1, "shift_uses_vy")?,
+        index_increment,
+        jump_uses_vx: flag(3, "jump_uses_vx")?,
+        clip_sprites: flag(4, "clip_sprites")?,
+    })
+}
+
+#[cfg(test)]
+#[path = "./movie_test.rs"]
+mod movie_test;
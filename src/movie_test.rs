@@ -0,0 +1,105 @@
+use super::*;
+use assembler::assemble;
+
+fn build_movie() -> Movie {
+    let mut movie = Movie::new(&[0x12, 0x00], 42, 700, Quirks::SUPER_CHIP);
+    let mut keypad = [false; 16];
+    for _ in 0..3 {
+        movie.record(keypad);
+    }
+    keypad[5] = true;
+    keypad[0xA] = true;
+    movie.record(keypad);
+    movie.record([false; 16]);
+    movie
+}
+
+#[test]
+fn test_format() {
+    let text = build_movie().to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "CHIP8-MOVIE 2");
+    assert_eq!(lines[2], "seed 42");
+    assert_eq!(lines[3], "ips 700");
+    assert_eq!(
+        lines[4],
+        "quirks vf_reset=0 shift_uses_vy=0 index_increment=0 jump_uses_vx=1 clip_sprites=1"
+    );
+    assert_eq!(&lines[5..], ["0000 3", "0420 1", "0000 1"]);
+}
+
+#[test]
+fn test_round_trip() {
+    let movie = build_movie();
+    let parsed = Movie::parse(&movie.to_string()).unwrap();
+    assert_eq!(parsed, movie);
+    assert_eq!(parsed.len(), 5);
+    assert!(parsed.keypad(3).unwrap()[5]);
+    assert_eq!(parsed.keypad(5), None);
+}
+
+#[test]
+fn test_parse_errors() {
+    let text = build_movie().to_string();
+    match Movie::parse("hello") {
+        Err(MovieError::NotAMovie) => {}
+        other => panic!("expected NotAMovie, got {:?}", other),
+    }
+    match Movie::parse(&text.replacen("MOVIE 2", "MOVIE 1", 1)) {
+        Err(MovieError::UnsupportedVersion(1)) => {}
+        other => panic!("expected UnsupportedVersion, got {:?}", other),
+    }
+    match Movie::parse(&text.replacen("seed 42", "seed x", 1)) {
+        Err(MovieError::Invalid(3)) => {}
+        other => panic!("expected Invalid(3), got {:?}", other),
+    }
+    match Movie::parse(&format!("{}zz 1\n", text)) {
+        Err(MovieError::Invalid(9)) => {}
+        other => panic!("expected Invalid(9), got {:?}", other),
+    }
+    match Movie::parse(&format!("{}0000 99999999999999\n", text)) {
+        Err(MovieError::Invalid(9)) => {}
+        other => panic!("expected Invalid(9), got {:?}", other),
+    }
+}
+
+#[test]
+fn test_wrong_rom() {
+    match build_movie().start(&[0x00, 0xE0]) {
+        Err(MovieError::WrongRom) => {}
+        other => panic!("expected WrongRom, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_replay_is_deterministic() {
+    // Draws random bytes until a key is held, then stops on it.
+    let rom = assemble(
+        "loop: RND V0, #FF\n\
+         LD I, #300\n\
+         LD B, V0\n\
+         SKP V1\n\
+         JP loop\n\
+         EXIT",
+    )
+    .unwrap();
+    let mut movie = Movie::new(&rom, 7, 600, Quirks::default());
+    let mut processor = Processor::new(movie.quirks);
+    processor.seed(movie.seed);
+    processor.load(&rom);
+    let mut clock = Clock::new(movie.instructions_per_second);
+    for frame in 0..20 {
+        let mut keypad = [false; 16];
+        keypad[0] = frame >= 12;
+        movie.record(keypad);
+        processor.run_frame(keypad, &mut clock).unwrap();
+    }
+
+    let movie = Movie::parse(&movie.to_string()).unwrap();
+    let (mut replay, mut clock) = movie.start(&rom).unwrap();
+    while let Some(keypad) = movie.keypad(clock.frames()) {
+        replay.run_frame(keypad, &mut clock).unwrap();
+    }
+    assert!(replay.exited());
+    assert_eq!(replay.save_state(), processor.save_state());
+}
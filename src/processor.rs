@@ -1,17 +1,61 @@
-use font::FONT_SET;
-use rand;
-use rand::Rng;
-
+use clock::Clock;
+use error::{EmulatorError, ErrorKind};
+use font::{BIG_FONT_SET, FONT_SET};
+use instruction::Instruction;
+use modules::rom_hash;
+use quirks::{IndexIncrement, Quirks};
+use random::{Entropy, RandomSource, Seeded};
+use savestate::{StateError, StateReader, StateWriter};
+use std::mem;
+use std::ops::Range;
+
+use Vram;
 use CHIP8_HEIGHT;
-use CHIP8_MEMORY;
 use CHIP8_WIDTH;
+use PROGRAM_START;
+use SCHIP_HEIGHT;
+use SCHIP_WIDTH;
+use XOCHIP_MEMORY;
 
 const OPCODE_SIZE: usize = 2;
+const BIG_FONT_ADDR: usize = FONT_SET.len();
+
+// Pitch at which the audio pattern plays back at 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+// Until a ROM loads its own pattern, play a 250 Hz square wave.
+pub const DEFAULT_AUDIO_PATTERN: [u8; 16] = [
+    0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00,
+];
 
 pub struct OutputState<'a> {
-    pub vram: &'a [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+    pub vram: &'a Vram,
+    pub width: usize,
+    pub height: usize,
     pub vram_changed: bool,
     pub beep: bool,
+    pub audio_pattern: &'a [u8; 16],
+    pub pitch: u8,
+    pub exited: bool,
+}
+
+/// The sound timer starting or stopping, `cycle` instructions into a
+/// `SoundSpan`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SoundEvent {
+    pub cycle: u32,
+    pub on: bool,
+}
+
+/// When the beep was on over a stretch of emulated time: whether it was on
+/// at the start, how many instructions the stretch lasted, and every change
+/// in between, in order. The 60 Hz timer ticks fall at the end of each
+/// frame's instructions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SoundSpan {
+    pub cycles: u32,
+    pub start_on: bool,
+    pub events: Vec<SoundEvent>,
 }
 
 enum ProgramCounter {
@@ -20,20 +64,25 @@ enum ProgramCounter {
     Jump(usize),
 }
 
+type OpResult = Result<ProgramCounter, ErrorKind>;
+
 impl ProgramCounter {
-    fn skip_if(condition: bool) -> ProgramCounter {
+    fn skip_if(condition: bool) -> OpResult {
         if condition {
-            ProgramCounter::Skip
+            Ok(ProgramCounter::Skip)
         } else {
-            ProgramCounter::Next
+            Ok(ProgramCounter::Next)
         }
     }
 }
 
 pub struct Processor {
-    vram: [[u8; CHIP8_WIDTH]; CHIP8_HEIGHT],
+    vram: Vram,
     vram_changed: bool,
-    ram: [u8; CHIP8_MEMORY],
+    hires: bool,
+    exited: bool,
+    planes: u8,
+    ram: [u8; XOCHIP_MEMORY],
     stack: [usize; 16],
     v: [u8; 16],
     i: usize,
@@ -44,364 +93,930 @@ pub struct Processor {
     keypad: [bool; 16],
     keypad_waiting: bool,
     keypad_register: usize,
+    rpl: [u8; 16],
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    quirks: Quirks,
+    // The `rom_hash` of what `load` was last given; save states must match it.
+    rom_hash: u64,
+    random: Box<dyn RandomSource>,
+    // The sound since the last `take_sound`.
+    sound: SoundSpan,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new(Quirks::default())
+    }
 }
 
 impl Processor {
-    pub fn new() -> Self {
-        let mut ram = [0u8; CHIP8_MEMORY];
-        for i in 0..FONT_SET.len() {
-            ram[i] = FONT_SET[i];
-        }
+    pub fn new(quirks: Quirks) -> Self {
+        let mut ram = [0u8; XOCHIP_MEMORY];
+        ram[..FONT_SET.len()].copy_from_slice(&FONT_SET);
+        ram[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONT_SET.len()].copy_from_slice(&BIG_FONT_SET);
 
         Processor {
-            vram: [[0; CHIP8_WIDTH]; CHIP8_HEIGHT],
+            vram: [[0; SCHIP_WIDTH]; SCHIP_HEIGHT],
             vram_changed: false,
-            ram: ram,
+            hires: false,
+            exited: false,
+            planes: 1,
+            ram,
             stack: [0; 16],
             v: [0; 16],
             i: 0,
-            pc: 0x200,
+            pc: PROGRAM_START,
             sp: 0,
             delay_timer: 0,
             sound_timer: 0,
             keypad: [false; 16],
             keypad_waiting: false,
             keypad_register: 0,
+            rpl: [0; 16],
+            audio_pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            quirks,
+            rom_hash: rom_hash(&[]),
+            random: Box::new(Entropy::new()),
+            sound: SoundSpan::default(),
         }
     }
 
+    /// Reseeds the generator behind RND, so runs with the same seed and input
+    /// are identical.
+    pub fn seed(&mut self, seed: u64) {
+        self.random = Box::new(Seeded::new(seed));
+    }
+
+    /// Replaces where RND gets its bytes from; new processors use OS entropy.
+    pub fn set_random_source(&mut self, random: Box<dyn RandomSource>) {
+        self.random = random;
+    }
+
+    /// Restarts the loaded program, as a reset button would: RAM, the RPL
+    /// flags, the quirks and the random source are kept, and everything else
+    /// is as `new` left it.
+    pub fn reset(&mut self) {
+        let mut fresh = Processor::new(self.quirks);
+        fresh.ram = self.ram;
+        fresh.rpl = self.rpl;
+        fresh.rom_hash = self.rom_hash;
+        mem::swap(&mut fresh.random, &mut self.random);
+        self.replace(fresh);
+    }
+
     pub fn load(&mut self, data: &[u8]) {
+        self.rom_hash = rom_hash(data);
         for (i, &byte) in data.iter().enumerate() {
-            let addr = 0x200 + i;
-            if addr < 4096 {
-                self.ram[0x200 + i] = byte;
+            let addr = PROGRAM_START + i;
+            if addr < XOCHIP_MEMORY {
+                self.ram[addr] = byte;
             } else {
                 break;
             }
         }
     }
 
-    pub fn tick(&mut self, keypad: [bool; 16]) -> OutputState {
+    /// Executes one instruction. On a fault the registers, I, PC, stack and
+    /// memory are left as they were before the faulting instruction, so they
+    /// can be inspected; the keypad and cycle count are already updated.
+    pub fn tick(&mut self, keypad: [bool; 16]) -> Result<OutputState<'_>, EmulatorError> {
         self.keypad = keypad;
         self.vram_changed = false;
+        self.sound.cycles = self.sound.cycles.saturating_add(1);
+
+        if self.exited {
+            return Ok(self.output(false));
+        }
 
         if self.keypad_waiting {
-            for i in 0..keypad.len() {
-                if keypad[i] {
-                    self.v[self.keypad_register] = i as u8;
-                    self.keypad_waiting = false;
-                    break;
-                }
+            if let Some(key) = keypad.iter().position(|&pressed| pressed) {
+                self.v[self.keypad_register] = key as u8;
+                self.keypad_waiting = false;
             }
         } else {
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
-            let opcode = self.get_opcode();
-            self.run_opcode(opcode);
+            let opcode = self.opcode_at(self.pc).map_err(|kind| EmulatorError {
+                kind,
+                pc: self.pc,
+                opcode: 0,
+            })?;
+            self.run_opcode(opcode)?;
         }
 
+        Ok(self.output(self.vram_changed))
+    }
+
+    /// Counts the delay and sound timers down by one; call at 60 Hz.
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+            self.note_sound(true);
+        }
+    }
+
+    /// Runs one 60 Hz frame: as many instructions as the clock allows, then
+    /// one timer tick. `vram_changed` reports whether any of them drew.
+    pub fn run_frame(
+        &mut self,
+        keypad: [bool; 16],
+        clock: &mut Clock,
+    ) -> Result<OutputState<'_>, EmulatorError> {
+        let mut vram_changed = false;
+        for _ in 0..clock.next_frame() {
+            vram_changed |= self.tick(keypad)?.vram_changed;
+        }
+        self.tick_timers();
+        Ok(self.output(vram_changed))
+    }
+
+    /// Serializes the complete processor state, quirks and the position of a
+    /// seeded RND generator included.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(self.rom_hash);
+        state.bool(self.quirks.vf_reset);
+        state.bool(self.quirks.shift_uses_vy);
+        state.u8(match self.quirks.index_increment {
+            IndexIncrement::Unchanged => 0,
+            IndexIncrement::ByX => 1,
+            IndexIncrement::ByXPlusOne => 2,
+        });
+        state.bool(self.quirks.jump_uses_vx);
+        state.bool(self.quirks.clip_sprites);
+
+        state.bytes(&self.ram);
+        for row in self.vram.iter() {
+            state.bytes(row);
+        }
+        state.bool(self.hires);
+        state.bool(self.exited);
+        state.u8(self.planes);
+        state.bytes(&self.v);
+        state.u32(self.i as u32);
+        state.u32(self.pc as u32);
+        state.u8(self.sp as u8);
+        for &addr in self.stack.iter() {
+            state.u32(addr as u32);
+        }
+        state.u8(self.delay_timer);
+        state.u8(self.sound_timer);
+        for &pressed in self.keypad.iter() {
+            state.bool(pressed);
+        }
+        state.bool(self.keypad_waiting);
+        state.u8(self.keypad_register as u8);
+        state.bytes(&self.rpl);
+        state.bytes(&self.audio_pattern);
+        state.u8(self.pitch);
+        let position = self.random.position();
+        state.bool(position.is_some());
+        state.u64(position.unwrap_or(0));
+        state.finish()
+    }
+
+    /// Restores a state produced by `save_state` with the same ROM loaded.
+    /// The processor is only changed if the whole state is valid.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut state = StateReader::new(data)?;
+        if state.rom_hash() != self.rom_hash {
+            return Err(StateError::WrongRom);
+        }
+        let quirks = Quirks {
+            vf_reset: state.bool()?,
+            shift_uses_vy: state.bool()?,
+            index_increment: match state.u8()? {
+                0 => IndexIncrement::Unchanged,
+                1 => IndexIncrement::ByX,
+                2 => IndexIncrement::ByXPlusOne,
+                _ => return Err(StateError::Corrupt("quirk")),
+            },
+            jump_uses_vx: state.bool()?,
+            clip_sprites: state.bool()?,
+        };
+
+        let mut processor = Processor::new(quirks);
+        processor.rom_hash = self.rom_hash;
+        processor.ram.copy_from_slice(state.bytes(XOCHIP_MEMORY)?);
+        for row in processor.vram.iter_mut() {
+            row.copy_from_slice(state.bytes(SCHIP_WIDTH)?);
+        }
+        processor.hires = state.bool()?;
+        processor.exited = state.bool()?;
+        processor.planes = state.u8()?;
+        processor.v.copy_from_slice(state.bytes(16)?);
+        processor.i = state.u32()? as usize;
+        processor.pc = state.u32()? as usize;
+        processor.sp = state.u8()? as usize;
+        for addr in processor.stack.iter_mut() {
+            *addr = state.u32()? as usize;
+        }
+        processor.delay_timer = state.u8()?;
+        processor.sound_timer = state.u8()?;
+        for pressed in processor.keypad.iter_mut() {
+            *pressed = state.bool()?;
+        }
+        processor.keypad_waiting = state.bool()?;
+        processor.keypad_register = state.u8()? as usize;
+        processor.rpl.copy_from_slice(state.bytes(16)?);
+        processor.audio_pattern.copy_from_slice(state.bytes(16)?);
+        processor.pitch = state.u8()?;
+        let has_position = state.bool()?;
+        let position = state.u64()?;
+        state.finish()?;
+
+        if processor.planes > 0x3 {
+            return Err(StateError::Corrupt("plane mask"));
+        }
+        if processor.vram.iter().flatten().any(|&cell| cell > 0x3) {
+            return Err(StateError::Corrupt("pixel"));
+        }
+        if processor.sp > processor.stack.len() {
+            return Err(StateError::Corrupt("stack pointer"));
+        }
+        if processor.keypad_register > 0xF {
+            return Err(StateError::Corrupt("key register"));
+        }
+
+        // Keep the current generator, but move it back to where it was if it
+        // can be replayed, so RND gives the same bytes again.
+        mem::swap(&mut processor.random, &mut self.random);
+        if has_position {
+            processor.random.seek(position);
+        }
+        self.replace(processor);
+        Ok(())
+    }
+
+    // Becomes processor, keeping the sound timeline going.
+    fn replace(&mut self, processor: Processor) {
+        let was_on = self.sound_timer > 0;
+        let sound = mem::take(&mut self.sound);
+        *self = processor;
+        self.sound = sound;
+        self.note_sound(was_on);
+        self.vram_changed = true;
+    }
+
+    /// Hands over the sound timeline since the last call, so audio can start
+    /// and stop the beep at the exact instruction the timer did.
+    pub fn take_sound(&mut self) -> SoundSpan {
+        let next = SoundSpan {
+            start_on: self.sound_timer > 0,
+            ..SoundSpan::default()
+        };
+        mem::replace(&mut self.sound, next)
+    }
+
+    // Records a change to the sound timer, if it started or stopped the beep.
+    fn note_sound(&mut self, was_on: bool) {
+        let on = self.sound_timer > 0;
+        if on != was_on {
+            self.sound.events.push(SoundEvent {
+                cycle: self.sound.cycles,
+                on,
+            });
+        }
+    }
+
+    pub fn vram(&self) -> &Vram {
+        &self.vram
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn v(&self) -> &[u8; 16] {
+        &self.v
+    }
+
+    pub fn i(&self) -> usize {
+        self.i
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn sp(&self) -> usize {
+        self.sp
+    }
+
+    /// The return addresses currently on the stack, oldest first.
+    pub fn stack(&self) -> &[usize] {
+        &self.stack[..self.sp]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// The register FX0A will store the next key press in, while it waits.
+    pub fn waiting_for_key(&self) -> Option<usize> {
+        if self.keypad_waiting {
+            Some(self.keypad_register)
+        } else {
+            None
+        }
+    }
+
+    pub fn hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Whether the program has run EXIT (00FD).
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Width of the active display resolution.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            SCHIP_WIDTH
+        } else {
+            CHIP8_WIDTH
+        }
+    }
+
+    /// Height of the active display resolution.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            SCHIP_HEIGHT
+        } else {
+            CHIP8_HEIGHT
+        }
+    }
+
+    /// What the front end needs to present the current state.
+    pub fn output(&self, vram_changed: bool) -> OutputState<'_> {
         OutputState {
             vram: &self.vram,
-            vram_changed: self.vram_changed,
+            width: self.width(),
+            height: self.height(),
+            vram_changed,
             beep: self.sound_timer > 0,
+            audio_pattern: &self.audio_pattern,
+            pitch: self.pitch,
+            exited: self.exited,
         }
     }
 
-    fn get_opcode(&self) -> u16 {
-        (self.ram[self.pc] as u16) << 8 | self.ram[self.pc + 1] as u16
-    }
-
-    fn run_opcode(&mut self, opcode: u16) {
-        let nibbles = (
-            (opcode & 0xF000) >> 12 as u8,
-            (opcode & 0x0F00) >> 8 as u8,
-            (opcode & 0x00F0) >> 4 as u8,
-            (opcode & 0x000F) as u8,
-        );
-        let nnn = (opcode & 0x0FFF) as usize;
-        let kk = (opcode & 0x00FF) as u8;
-        let x = nibbles.1 as usize;
-        let y = nibbles.2 as usize;
-        let n = nibbles.3 as usize;
-
-        let pc_change = match nibbles {
-            (0x00, 0x00, 0x0e, 0x00) => self.op_00e0(),
-            (0x00, 0x00, 0x0e, 0x0e) => self.op_00ee(),
-            (0x01, _, _, _) => self.op_1nnn(nnn),
-            (0x02, _, _, _) => self.op_2nnn(nnn),
-            (0x03, _, _, _) => self.op_3xkk(x, kk),
-            (0x04, _, _, _) => self.op_4xkk(x, kk),
-            (0x05, _, _, 0x00) => self.op_5xy0(x, y),
-            (0x06, _, _, _) => self.op_6xkk(x, kk),
-            (0x07, _, _, _) => self.op_7xkk(x, kk),
-            (0x08, _, _, 0x00) => self.op_8xy0(x, y),
-            (0x08, _, _, 0x01) => self.op_8xy1(x, y),
-            (0x08, _, _, 0x02) => self.op_8xy2(x, y),
-            (0x08, _, _, 0x03) => self.op_8xy3(x, y),
-            (0x08, _, _, 0x04) => self.op_8xy4(x, y),
-            (0x08, _, _, 0x05) => self.op_8xy5(x, y),
-            (0x08, _, _, 0x06) => self.op_8x06(x),
-            (0x08, _, _, 0x07) => self.op_8xy7(x, y),
-            (0x08, _, _, 0x0e) => self.op_8x0e(x),
-            (0x09, _, _, 0x00) => self.op_9xy0(x, y),
-            (0x0a, _, _, _) => self.op_annn(nnn),
-            (0x0b, _, _, _) => self.op_bnnn(nnn),
-            (0x0c, _, _, _) => self.op_cxkk(x, kk),
-            (0x0d, _, _, _) => self.op_dxyn(x, y, n),
-            (0x0e, _, 0x09, 0x0e) => self.op_ex9e(x),
-            (0x0e, _, 0x0a, 0x01) => self.op_exa1(x),
-            (0x0f, _, 0x00, 0x07) => self.op_fx07(x),
-            (0x0f, _, 0x00, 0x0a) => self.op_fx0a(x),
-            (0x0f, _, 0x01, 0x05) => self.op_fx15(x),
-            (0x0f, _, 0x01, 0x08) => self.op_fx18(x),
-            (0x0f, _, 0x01, 0x0e) => self.op_fx1e(x),
-            (0x0f, _, 0x02, 0x09) => self.op_fx29(x),
-            (0x0f, _, 0x03, 0x03) => self.op_fx33(x),
-            (0x0f, _, 0x05, 0x05) => self.op_fx55(x),
-            (0x0f, _, 0x06, 0x05) => self.op_fx65(x),
-            _ => ProgramCounter::Next,
+    /// Reads the two-byte opcode stored at addr.
+    pub fn opcode_at(&self, addr: usize) -> Result<u16, ErrorKind> {
+        let range = self.ram_range(addr, OPCODE_SIZE)?;
+        Ok((self.ram[range.start] as u16) << 8 | self.ram[range.start + 1] as u16)
+    }
+
+    // Checks that len bytes starting at addr are all in RAM.
+    fn ram_range(&self, addr: usize, len: usize) -> Result<Range<usize>, ErrorKind> {
+        if addr + len <= self.ram.len() {
+            Ok(addr..addr + len)
+        } else {
+            Err(ErrorKind::MemoryOutOfRange(addr.max(self.ram.len())))
+        }
+    }
+
+    fn key_pressed(&self, x: usize) -> Result<bool, ErrorKind> {
+        match self.keypad.get(self.v[x] as usize) {
+            Some(&pressed) => Ok(pressed),
+            None => Err(ErrorKind::InvalidKey(self.v[x])),
+        }
+    }
+
+    fn run_opcode(&mut self, opcode: u16) -> Result<(), EmulatorError> {
+        let pc_change = match Instruction::decode(opcode) {
+            Some(instruction) => self.execute(instruction),
+            None => Err(ErrorKind::UnknownOpcode),
         };
+        let pc_change = pc_change.map_err(|kind| EmulatorError {
+            kind,
+            pc: self.pc,
+            opcode,
+        })?;
 
         match pc_change {
             ProgramCounter::Next => self.pc += OPCODE_SIZE,
-            ProgramCounter::Skip => self.pc += 2 * OPCODE_SIZE,
+            // F000 NNNN is four bytes long, so skipping it takes two words.
+            ProgramCounter::Skip => {
+                let next = self.pc + OPCODE_SIZE;
+                self.pc = if self.opcode_at(next) == Ok(0xF000) {
+                    next + 2 * OPCODE_SIZE
+                } else {
+                    next + OPCODE_SIZE
+                };
+            }
             ProgramCounter::Jump(addr) => self.pc = addr,
         }
+        Ok(())
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> OpResult {
+        match instruction {
+            // Machine code routines can't run here; skipping them is what
+            // most interpreters do.
+            Instruction::Sys(_) => Ok(ProgramCounter::Next),
+            Instruction::ScrollDown(n) => self.op_00cn(n),
+            Instruction::ScrollUp(n) => self.op_00dn(n),
+            Instruction::Cls => self.op_00e0(),
+            Instruction::Ret => self.op_00ee(),
+            Instruction::ScrollRight => self.op_00fb(),
+            Instruction::ScrollLeft => self.op_00fc(),
+            Instruction::Exit => self.op_00fd(),
+            Instruction::Low => self.op_00fe(),
+            Instruction::High => self.op_00ff(),
+            Instruction::Jump(nnn) => self.op_1nnn(nnn),
+            Instruction::Call(nnn) => self.op_2nnn(nnn),
+            Instruction::SkipEqByte(x, kk) => self.op_3xkk(x, kk),
+            Instruction::SkipNeByte(x, kk) => self.op_4xkk(x, kk),
+            Instruction::SkipEq(x, y) => self.op_5xy0(x, y),
+            Instruction::SaveRange(x, y) => self.op_5xy2(x, y),
+            Instruction::LoadRange(x, y) => self.op_5xy3(x, y),
+            Instruction::LoadByte(x, kk) => self.op_6xkk(x, kk),
+            Instruction::AddByte(x, kk) => self.op_7xkk(x, kk),
+            Instruction::Move(x, y) => self.op_8xy0(x, y),
+            Instruction::Or(x, y) => self.op_8xy1(x, y),
+            Instruction::And(x, y) => self.op_8xy2(x, y),
+            Instruction::Xor(x, y) => self.op_8xy3(x, y),
+            Instruction::Add(x, y) => self.op_8xy4(x, y),
+            Instruction::Sub(x, y) => self.op_8xy5(x, y),
+            Instruction::Shr(x, y) => self.op_8xy6(x, y),
+            Instruction::Subn(x, y) => self.op_8xy7(x, y),
+            Instruction::Shl(x, y) => self.op_8xye(x, y),
+            Instruction::SkipNe(x, y) => self.op_9xy0(x, y),
+            Instruction::LoadI(nnn) => self.op_annn(nnn),
+            Instruction::JumpOffset(nnn) => self.op_bnnn(nnn >> 8, nnn),
+            Instruction::Random(x, kk) => self.op_cxkk(x, kk),
+            Instruction::Draw(x, y, n) => self.op_dxyn(x, y, n),
+            Instruction::SkipKey(x) => self.op_ex9e(x),
+            Instruction::SkipNoKey(x) => self.op_exa1(x),
+            Instruction::LoadLongI => self.op_f000(),
+            Instruction::Plane(n) => self.op_fn01(n),
+            Instruction::Audio => self.op_f002(),
+            Instruction::GetDelay(x) => self.op_fx07(x),
+            Instruction::WaitKey(x) => self.op_fx0a(x),
+            Instruction::SetDelay(x) => self.op_fx15(x),
+            Instruction::SetSound(x) => self.op_fx18(x),
+            Instruction::AddI(x) => self.op_fx1e(x),
+            Instruction::Font(x) => self.op_fx29(x),
+            Instruction::BigFont(x) => self.op_fx30(x),
+            Instruction::Bcd(x) => self.op_fx33(x),
+            Instruction::Pitch(x) => self.op_fx3a(x),
+            Instruction::Store(x) => self.op_fx55(x),
+            Instruction::Restore(x) => self.op_fx65(x),
+            Instruction::SaveFlags(x) => self.op_fx75(x),
+            Instruction::LoadFlags(x) => self.op_fx85(x),
+        }
+    }
+
+    //SCD nibble
+    fn op_00cn(&mut self, n: usize) -> OpResult {
+        self.scroll(0, n as isize);
+        Ok(ProgramCounter::Next)
+    }
+
+    //SCU nibble
+    fn op_00dn(&mut self, n: usize) -> OpResult {
+        self.scroll(0, -(n as isize));
+        Ok(ProgramCounter::Next)
     }
 
     //CLS
-    fn op_00e0(&mut self) -> ProgramCounter {
-        for y in 0..CHIP8_HEIGHT {
-            for x in 0..CHIP8_WIDTH {
-                self.vram[y][x] = 0;
+    fn op_00e0(&mut self) -> OpResult {
+        let planes = self.planes;
+        for row in self.vram.iter_mut() {
+            for cell in row.iter_mut() {
+                *cell &= !planes;
             }
         }
         self.vram_changed = true;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //RET
-    fn op_00ee(&mut self) -> ProgramCounter {
+    fn op_00ee(&mut self) -> OpResult {
+        if self.sp == 0 {
+            return Err(ErrorKind::StackUnderflow);
+        }
         self.sp -= 1;
-        ProgramCounter::Jump(self.stack[self.sp])
+        Ok(ProgramCounter::Jump(self.stack[self.sp]))
+    }
+
+    //SCR
+    fn op_00fb(&mut self) -> OpResult {
+        self.scroll(4, 0);
+        Ok(ProgramCounter::Next)
+    }
+
+    //SCL
+    fn op_00fc(&mut self) -> OpResult {
+        self.scroll(-4, 0);
+        Ok(ProgramCounter::Next)
+    }
+
+    //EXIT
+    fn op_00fd(&mut self) -> OpResult {
+        self.exited = true;
+        Ok(ProgramCounter::Next)
+    }
+
+    //LOW
+    fn op_00fe(&mut self) -> OpResult {
+        self.hires = false;
+        self.clear_screen();
+        Ok(ProgramCounter::Next)
+    }
+
+    //HIGH
+    fn op_00ff(&mut self) -> OpResult {
+        self.hires = true;
+        self.clear_screen();
+        Ok(ProgramCounter::Next)
+    }
+
+    fn clear_screen(&mut self) {
+        self.vram = [[0; SCHIP_WIDTH]; SCHIP_HEIGHT];
+        self.vram_changed = true;
+    }
+
+    // Moves the selected planes by (dx, dy) pixels, filling with blanks.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = (self.width() as isize, self.height() as isize);
+        let planes = self.planes;
+        let previous = self.vram;
+        for y in 0..height {
+            for x in 0..width {
+                let (from_x, from_y) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&from_x) && (0..height).contains(&from_y) {
+                    previous[from_y as usize][from_x as usize]
+                } else {
+                    0
+                };
+                let cell = &mut self.vram[y as usize][x as usize];
+                *cell = (*cell & !planes) | (moved & planes);
+            }
+        }
+        self.vram_changed = true;
     }
 
     //JP addr
-    fn op_1nnn(&self, addr: usize) -> ProgramCounter {
-        ProgramCounter::Jump(addr)
+    fn op_1nnn(&self, addr: usize) -> OpResult {
+        Ok(ProgramCounter::Jump(addr))
     }
 
     //CALL addr
-    fn op_2nnn(&mut self, addr: usize) -> ProgramCounter {
+    fn op_2nnn(&mut self, addr: usize) -> OpResult {
+        if self.sp == self.stack.len() {
+            return Err(ErrorKind::StackOverflow);
+        }
         self.stack[self.sp] = self.pc + OPCODE_SIZE;
         self.sp += 1;
-        ProgramCounter::Jump(addr)
+        Ok(ProgramCounter::Jump(addr))
     }
 
     // SE Vx, byte
-    fn op_3xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
+    fn op_3xkk(&mut self, x: usize, kk: u8) -> OpResult {
         ProgramCounter::skip_if(self.v[x] == kk)
     }
 
     // SNE
-    fn op_4xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
+    fn op_4xkk(&mut self, x: usize, kk: u8) -> OpResult {
         ProgramCounter::skip_if(self.v[x] != kk)
     }
 
     // SE Vx, Vy
-    fn op_5xy0(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_5xy0(&mut self, x: usize, y: usize) -> OpResult {
         ProgramCounter::skip_if(self.v[x] == self.v[y])
     }
 
+    //SAVE Vx - Vy
+    fn op_5xy2(&mut self, x: usize, y: usize) -> OpResult {
+        let range = self.ram_range(self.i, x.abs_diff(y) + 1)?;
+        for (addr, register) in range.zip(register_range(x, y)) {
+            self.ram[addr] = self.v[register];
+        }
+        Ok(ProgramCounter::Next)
+    }
+
+    //LOAD Vx - Vy
+    fn op_5xy3(&mut self, x: usize, y: usize) -> OpResult {
+        let range = self.ram_range(self.i, x.abs_diff(y) + 1)?;
+        for (addr, register) in range.zip(register_range(x, y)) {
+            self.v[register] = self.ram[addr];
+        }
+        Ok(ProgramCounter::Next)
+    }
+
     //LD Vx, byte
-    fn op_6xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
+    fn op_6xkk(&mut self, x: usize, kk: u8) -> OpResult {
         self.v[x] = kk;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //ADD Vx, byte
-    fn op_7xkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
+    fn op_7xkk(&mut self, x: usize, kk: u8) -> OpResult {
         let result = self.v[x] as u16 + kk as u16;
         self.v[x] = result as u8;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //LD Vx, Vy
-    fn op_8xy0(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_8xy0(&mut self, x: usize, y: usize) -> OpResult {
         self.v[x] = self.v[y];
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //OR Vx, Vy
-    fn op_8xy1(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_8xy1(&mut self, x: usize, y: usize) -> OpResult {
         self.v[x] |= self.v[y];
-        ProgramCounter::Next
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        Ok(ProgramCounter::Next)
     }
 
     //AND Vx, Vy
-    fn op_8xy2(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_8xy2(&mut self, x: usize, y: usize) -> OpResult {
         self.v[x] &= self.v[y];
-        ProgramCounter::Next
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        Ok(ProgramCounter::Next)
     }
 
     //XOR Vx, Vy
-    fn op_8xy3(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_8xy3(&mut self, x: usize, y: usize) -> OpResult {
         self.v[x] ^= self.v[y];
-        ProgramCounter::Next
+        if self.quirks.vf_reset {
+            self.v[0xF] = 0;
+        }
+        Ok(ProgramCounter::Next)
     }
 
     //ADD Vx, Vy
-    fn op_8xy4(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_8xy4(&mut self, x: usize, y: usize) -> OpResult {
         let result = self.v[x] as u16 + self.v[y] as u16;
         self.v[x] = result as u8;
         self.v[0xF] = if result > 0xFF { 1 } else { 0 };
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //SUB Vx, Vy
-    fn op_8xy5(&mut self, x: usize, y: usize) -> ProgramCounter {
-        self.v[0xF] = if self.v[x] > self.v[y] { 1 } else { 0 };
-        self.v[x] = self.v[x].wrapping_sub(self.v[y]);
-        ProgramCounter::Next
+    fn op_8xy5(&mut self, x: usize, y: usize) -> OpResult {
+        let (result, borrow) = self.v[x].overflowing_sub(self.v[y]);
+        self.v[x] = result;
+        self.v[0xF] = if borrow { 0 } else { 1 };
+        Ok(ProgramCounter::Next)
     }
 
-    //SHR Vx  , Vy
-    fn op_8x06(&mut self, x: usize) -> ProgramCounter {
-        self.v[0xF] = self.v[x] & 0x1;
-        self.v[x] >>= 1;
-        ProgramCounter::Next
+    //SHR Vx {, Vy}
+    fn op_8xy6(&mut self, x: usize, y: usize) -> OpResult {
+        let value = if self.quirks.shift_uses_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        };
+        self.v[x] = value >> 1;
+        self.v[0xF] = value & 0x1;
+        Ok(ProgramCounter::Next)
     }
 
     //SUBN Vx, Vy
-    fn op_8xy7(&mut self, x: usize, y: usize) -> ProgramCounter {
-        self.v[0xF] = if self.v[y] > self.v[x] { 1 } else { 0 };
-        self.v[x] = self.v[y].wrapping_sub(self.v[x]);
-        ProgramCounter::Next
+    fn op_8xy7(&mut self, x: usize, y: usize) -> OpResult {
+        let (result, borrow) = self.v[y].overflowing_sub(self.v[x]);
+        self.v[x] = result;
+        self.v[0xF] = if borrow { 0 } else { 1 };
+        Ok(ProgramCounter::Next)
     }
 
-    //SHL Vx, Vy
-    fn op_8x0e(&mut self, x: usize) -> ProgramCounter {
-        self.v[0xF] = self.v[x] >> 7;
-        self.v[x] <<= 1;
-        ProgramCounter::Next
+    //SHL Vx {, Vy}
+    fn op_8xye(&mut self, x: usize, y: usize) -> OpResult {
+        let value = if self.quirks.shift_uses_vy {
+            self.v[y]
+        } else {
+            self.v[x]
+        };
+        self.v[x] = value << 1;
+        self.v[0xF] = value >> 7;
+        Ok(ProgramCounter::Next)
     }
 
     //SNE Vx, Vy
-    fn op_9xy0(&mut self, x: usize, y: usize) -> ProgramCounter {
+    fn op_9xy0(&mut self, x: usize, y: usize) -> OpResult {
         ProgramCounter::skip_if(self.v[x] != self.v[y])
     }
 
     //LD I, addr
-    fn op_annn(&mut self, addr: usize) -> ProgramCounter {
+    fn op_annn(&mut self, addr: usize) -> OpResult {
         self.i = addr;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //JP V0, addr
-    fn op_bnnn(&self, addr: usize) -> ProgramCounter {
-        ProgramCounter::Jump(addr + self.v[0] as usize)
+    fn op_bnnn(&self, x: usize, addr: usize) -> OpResult {
+        let offset = if self.quirks.jump_uses_vx {
+            self.v[x]
+        } else {
+            self.v[0]
+        };
+        Ok(ProgramCounter::Jump(addr + offset as usize))
     }
 
     //RND Vx, byte
-    fn op_cxkk(&mut self, x: usize, kk: u8) -> ProgramCounter {
-        let mut rng = rand::thread_rng();
-        self.v[x] = rng.gen::<u8>() & kk;
-        ProgramCounter::Next
+    fn op_cxkk(&mut self, x: usize, kk: u8) -> OpResult {
+        self.v[x] = self.random.next_byte() & kk;
+        Ok(ProgramCounter::Next)
     }
 
     //DRW Vx, Vy, nibble
-    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> ProgramCounter {
+    // A nibble of 0 draws a 16x16 SUPER-CHIP sprite, stored as 16 rows of two bytes.
+    // With both XO-CHIP planes selected, the second plane's sprite follows the first.
+    fn op_dxyn(&mut self, x: usize, y: usize, n: usize) -> OpResult {
+        let (width, height) = (self.width(), self.height());
+        let (rows, columns) = if n == 0 { (16, 16) } else { (n, 8) };
+        let origin_x = self.v[x] as usize % width;
+        let origin_y = self.v[y] as usize % height;
+        let clip = self.quirks.clip_sprites;
+        let sprite_len = rows * columns / 8;
+        let mut sprite = self
+            .ram_range(self.i, sprite_len * self.planes.count_ones() as usize)?
+            .start;
         self.v[0xF] = 0;
-        for byte in 0..n {
-            let y = (self.v[y] as usize + byte) % CHIP8_HEIGHT;
-            for bit in 0..8 {
-                let x = (self.v[x] as usize + bit) % CHIP8_WIDTH;
-                let color = (self.ram[self.i + byte] >> (7 - bit)) & 1;
-                self.v[0xF] |= color & self.vram[y][x];
-                self.vram[y][x] ^= color;
+        for plane in (0..2).map(|bit| 1u8 << bit) {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for row in 0..rows {
+                if clip && origin_y + row >= height {
+                    break;
+                }
+                let y = (origin_y + row) % height;
+                for column in 0..columns {
+                    if clip && origin_x + column >= width {
+                        break;
+                    }
+                    let x = (origin_x + column) % width;
+                    let byte = self.ram[sprite + row * columns / 8 + column / 8];
+                    if (byte >> (7 - column % 8)) & 1 == 0 {
+                        continue;
+                    }
+                    if self.vram[y][x] & plane != 0 {
+                        self.v[0xF] = 1;
+                    }
+                    self.vram[y][x] ^= plane;
+                }
             }
+            sprite += sprite_len;
         }
         self.vram_changed = true;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //SKP Vx
-    fn op_ex9e(&mut self, x: usize) -> ProgramCounter {
-        ProgramCounter::skip_if(self.keypad[self.v[x] as usize])
+    fn op_ex9e(&mut self, x: usize) -> OpResult {
+        ProgramCounter::skip_if(self.key_pressed(x)?)
     }
 
     //SKNP Vx
-    fn op_exa1(&mut self, x: usize) -> ProgramCounter {
-        ProgramCounter::skip_if(!self.keypad[self.v[x] as usize])
+    fn op_exa1(&mut self, x: usize) -> OpResult {
+        ProgramCounter::skip_if(!self.key_pressed(x)?)
+    }
+
+    //LD I, long addr
+    fn op_f000(&mut self) -> OpResult {
+        self.i = self.opcode_at(self.pc + OPCODE_SIZE)? as usize;
+        Ok(ProgramCounter::Jump(self.pc + 2 * OPCODE_SIZE))
+    }
+
+    //PLANE n
+    fn op_fn01(&mut self, n: usize) -> OpResult {
+        self.planes = n as u8 & 0x3;
+        Ok(ProgramCounter::Next)
+    }
+
+    //AUDIO
+    fn op_f002(&mut self) -> OpResult {
+        let range = self.ram_range(self.i, self.audio_pattern.len())?;
+        self.audio_pattern.copy_from_slice(&self.ram[range]);
+        Ok(ProgramCounter::Next)
     }
 
     //LD Vx, DT
-    fn op_fx07(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx07(&mut self, x: usize) -> OpResult {
         self.v[x] = self.delay_timer;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //LD Vx, K
-    fn op_fx0a(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx0a(&mut self, x: usize) -> OpResult {
         self.keypad_waiting = true;
         self.keypad_register = x;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //LD DT, Vx
-    fn op_fx15(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx15(&mut self, x: usize) -> OpResult {
         self.delay_timer = self.v[x];
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //LD ST, Vx
-    fn op_fx18(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx18(&mut self, x: usize) -> OpResult {
+        let was_on = self.sound_timer > 0;
         self.sound_timer = self.v[x];
-        ProgramCounter::Next
+        self.note_sound(was_on);
+        Ok(ProgramCounter::Next)
     }
 
     //ADD I, Vx
-    fn op_fx1e(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx1e(&mut self, x: usize) -> OpResult {
         self.i += self.v[x] as usize;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
     }
 
     //LD F, Vx
-    fn op_fx29(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx29(&mut self, x: usize) -> OpResult {
         self.i = self.v[x] as usize * 5;
-        ProgramCounter::Next
+        Ok(ProgramCounter::Next)
+    }
+
+    //LD HF, Vx
+    fn op_fx30(&mut self, x: usize) -> OpResult {
+        self.i = BIG_FONT_ADDR + self.v[x] as usize * 10;
+        Ok(ProgramCounter::Next)
     }
 
     //LD B, Vx
-    fn op_fx33(&mut self, x: usize) -> ProgramCounter {
+    fn op_fx33(&mut self, x: usize) -> OpResult {
+        let range = self.ram_range(self.i, 3)?;
         let value = self.v[x];
-        self.ram[self.i] = value / 100;
-        self.ram[self.i + 1] = (value / 10) % 10;
-        self.ram[self.i + 2] = value % 10;
-        ProgramCounter::Next
+        self.ram[range].copy_from_slice(&[value / 100, (value / 10) % 10, value % 10]);
+        Ok(ProgramCounter::Next)
+    }
+
+    //PITCH Vx
+    fn op_fx3a(&mut self, x: usize) -> OpResult {
+        self.pitch = self.v[x];
+        Ok(ProgramCounter::Next)
     }
 
     //LD [I], Vx
-    fn op_fx55(&mut self, x: usize) -> ProgramCounter {
-        for i in 0..=x {
-            self.ram[self.i + i] = self.v[i];
-        }
-        ProgramCounter::Next
+    fn op_fx55(&mut self, x: usize) -> OpResult {
+        let range = self.ram_range(self.i, x + 1)?;
+        self.ram[range].copy_from_slice(&self.v[..=x]);
+        self.increment_index(x);
+        Ok(ProgramCounter::Next)
     }
 
     //LD Vx, [I]
-    fn op_fx65(&mut self, x: usize) -> ProgramCounter {
-        for i in 0..=x {
-            self.v[i] = self.ram[self.i + i];
+    fn op_fx65(&mut self, x: usize) -> OpResult {
+        let range = self.ram_range(self.i, x + 1)?;
+        self.v[..=x].copy_from_slice(&self.ram[range]);
+        self.increment_index(x);
+        Ok(ProgramCounter::Next)
+    }
+
+    //LD R, Vx
+    fn op_fx75(&mut self, x: usize) -> OpResult {
+        self.rpl[..=x].copy_from_slice(&self.v[..=x]);
+        Ok(ProgramCounter::Next)
+    }
+
+    //LD Vx, R
+    fn op_fx85(&mut self, x: usize) -> OpResult {
+        self.v[..=x].copy_from_slice(&self.rpl[..=x]);
+        Ok(ProgramCounter::Next)
+    }
+
+    fn increment_index(&mut self, x: usize) {
+        match self.quirks.index_increment {
+            IndexIncrement::Unchanged => {}
+            IndexIncrement::ByX => self.i += x,
+            IndexIncrement::ByXPlusOne => self.i += x + 1,
         }
-        ProgramCounter::Next
     }
 }
 
+// Registers x through y inclusive, counting down when x > y.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    let forward = x <= y;
+    (0..=x.abs_diff(y)).map(move |offset| if forward { x + offset } else { x - offset })
+}
+
 #[cfg(test)]
 #[path = "./processor_test.rs"]
 mod processor_test;
@@ -1,16 +1,21 @@
 use super::*;
+use random::Scripted;
+use savestate::MAGIC;
 const START_PC: usize = 0xF00;
 const NEXT_PC: usize = START_PC + OPCODE_SIZE;
 const SKIPPED_PC: usize = START_PC + (OPCODE_SIZE * 2);
 fn build_processor() -> Processor {
-    let mut processor = Processor::new();
+    build_processor_with(Quirks::default())
+}
+fn build_processor_with(quirks: Quirks) -> Processor {
+    let mut processor = Processor::new(quirks);
     processor.pc = START_PC;
     processor.v = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7];
     processor
 }
 #[test]
 fn test_initial_state() {
-    let processor = Processor::new();
+    let processor = Processor::new(Quirks::default());
     assert_eq!(processor.pc, 0x200);
     assert_eq!(processor.sp, 0);
     assert_eq!(processor.stack, [0; 16]);
@@ -23,10 +28,17 @@ fn test_initial_state() {
     );
 }
 
+// Embedders can run the processor on a thread of their own.
+#[test]
+fn test_processor_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Processor>();
+}
+
 #[test]
 fn test_load_data() {
-    let mut processor = Processor::new();
-    processor.load(vec![1, 2, 3]);
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&[1, 2, 3]);
     assert_eq!(processor.ram[0x200], 1);
     assert_eq!(processor.ram[0x201], 2);
     assert_eq!(processor.ram[0x202], 3);
@@ -36,11 +48,11 @@ fn test_load_data() {
 #[test]
 fn test_op_00e0() {
     let mut processor = build_processor();
-    processor.vram = [[128; CHIP8_WIDTH]; CHIP8_HEIGHT];
-    processor.run_opcode(0x00E0);
+    processor.vram = [[1; SCHIP_WIDTH]; SCHIP_HEIGHT];
+    processor.run_opcode(0x00E0).unwrap();
 
-    for y in 0..CHIP8_HEIGHT {
-        for x in 0..CHIP8_WIDTH {
+    for y in 0..SCHIP_HEIGHT {
+        for x in 0..SCHIP_WIDTH {
             assert_eq!(processor.vram[y][x], 0);
         }
     }
@@ -53,7 +65,7 @@ fn test_op_00ee() {
     let mut processor = build_processor();
     processor.sp = 5;
     processor.stack[4] = 0x6666;
-    processor.run_opcode(0x00EE);
+    processor.run_opcode(0x00EE).unwrap();
     assert_eq!(processor.pc, 0x6666);
     assert_eq!(processor.sp, 4);
 }
@@ -62,7 +74,7 @@ fn test_op_00ee() {
 #[test]
 fn test_op_1nnn() {
     let mut processor = build_processor();
-    processor.run_opcode(0x1666);
+    processor.run_opcode(0x1666).unwrap();
     assert_eq!(processor.pc, 0x0666);
 }
 
@@ -70,7 +82,7 @@ fn test_op_1nnn() {
 #[test]
 fn test_op_2nnn() {
     let mut processor = build_processor();
-    processor.run_opcode(0x2666);
+    processor.run_opcode(0x2666).unwrap();
     assert_eq!(processor.pc, 0x0666);
     assert_eq!(processor.sp, 1);
     assert_eq!(processor.stack[0], NEXT_PC);
@@ -80,10 +92,10 @@ fn test_op_2nnn() {
 #[test]
 fn test_op_3xkk() {
     let mut processor = build_processor();
-    processor.run_opcode(0x3201);
+    processor.run_opcode(0x3201).unwrap();
     assert_eq!(processor.pc, SKIPPED_PC);
     let mut processor = build_processor();
-    processor.run_opcode(0x3200);
+    processor.run_opcode(0x3200).unwrap();
     assert_eq!(processor.pc, NEXT_PC);
 }
 
@@ -91,10 +103,10 @@ fn test_op_3xkk() {
 #[test]
 fn test_op_4xkk() {
     let mut processor = build_processor();
-    processor.run_opcode(0x4200);
+    processor.run_opcode(0x4200).unwrap();
     assert_eq!(processor.pc, SKIPPED_PC);
     let mut processor = build_processor();
-    processor.run_opcode(0x4201);
+    processor.run_opcode(0x4201).unwrap();
     assert_eq!(processor.pc, NEXT_PC);
 }
 
@@ -102,10 +114,10 @@ fn test_op_4xkk() {
 #[test]
 fn test_op_5xy0() {
     let mut processor = build_processor();
-    processor.run_opcode(0x5540);
+    processor.run_opcode(0x5540).unwrap();
     assert_eq!(processor.pc, SKIPPED_PC);
     let mut processor = build_processor();
-    processor.run_opcode(0x5500);
+    processor.run_opcode(0x5500).unwrap();
     assert_eq!(processor.pc, NEXT_PC);
 }
 
@@ -113,7 +125,7 @@ fn test_op_5xy0() {
 #[test]
 fn test_op_6xkk() {
     let mut processor = build_processor();
-    processor.run_opcode(0x65ff);
+    processor.run_opcode(0x65ff).unwrap();
     assert_eq!(processor.v[5], 0xff);
     assert_eq!(processor.pc, NEXT_PC);
 }
@@ -122,7 +134,7 @@ fn test_op_6xkk() {
 #[test]
 fn test_op_7xkk() {
     let mut processor = build_processor();
-    processor.run_opcode(0x75f0);
+    processor.run_opcode(0x75f0).unwrap();
     assert_eq!(processor.v[5], 0xf2);
     assert_eq!(processor.pc, NEXT_PC);
 }
@@ -131,17 +143,21 @@ fn test_op_7xkk() {
 #[test]
 fn test_op_8xy0() {
     let mut processor = build_processor();
-    processor.run_opcode(0x8050);
+    processor.run_opcode(0x8050).unwrap();
     assert_eq!(processor.v[0], 0x02);
     assert_eq!(processor.pc, NEXT_PC);
 }
 
 fn check_math(v1: u8, v2: u8, op: u16, result: u8, vf: u8) {
-    let mut processor = build_processor();
+    check_math_with(Quirks::default(), v1, v2, op, result, vf);
+}
+
+fn check_math_with(quirks: Quirks, v1: u8, v2: u8, op: u16, result: u8, vf: u8) {
+    let mut processor = build_processor_with(quirks);
     processor.v[0] = v1;
     processor.v[1] = v2;
     processor.v[0x0F] = 0;
-    processor.run_opcode(0x8010 + op);
+    processor.run_opcode(0x8010 + op).unwrap();
     assert_eq!(processor.v[0], result);
     assert_eq!(processor.v[0x0F], vf);
     assert_eq!(processor.pc, NEXT_PC);
@@ -150,7 +166,7 @@ fn check_math(v1: u8, v2: u8, op: u16, result: u8, vf: u8) {
 // OR Vx, Vy - Set Vx = Vx OR Vy
 #[test]
 fn test_op_8xy1() {
-    check_math(0x0F, 0xF0, 1, 0);
+    check_math(0x0F, 0xF0, 1, 0xFF, 0);
 }
 
 // AND Vx, Vy - Set Vx = Vx AND Vy
@@ -175,13 +191,711 @@ fn test_op_8xy4() {
 //Sub Vx, Vy - Set Vx = Vx - Vy, set VF = NOT borrow
 #[test]
 fn test_op_8xy5() {
-    check_math(0x0F, 0xF0, 5, 0x1F, 1);
-    check_math(0x0F, 0x0F, 5, 0x00, 0);
+    check_math(0xF0, 0x0F, 5, 0xE1, 1);
+    check_math(0x0F, 0xF0, 5, 0x1F, 0);
+    // Equal values don't borrow.
+    check_math(0x42, 0x42, 5, 0x00, 1);
 }
 
-// SHR Vx {, Vy} - Set Vx = Vx SHR 1
+//SUBN Vx, Vy - Set Vx = Vy - Vx, set VF = NOT borrow
+#[test]
+fn test_op_8xy7() {
+    check_math(0x0F, 0xF0, 7, 0xE1, 1);
+    check_math(0xF0, 0x0F, 7, 0x1F, 0);
+    check_math(0x42, 0x42, 7, 0x00, 1);
+}
+
+// The flag is written after the result, so it wins when VF is the target.
+#[test]
+fn test_flag_overwrites_vf() {
+    let mut processor = build_processor();
+    processor.v[0xF] = 0x10;
+    processor.v[1] = 0x20;
+    processor.run_opcode(0x8F15).unwrap();
+    assert_eq!(processor.v[0xF], 0);
+}
+
+// SHR Vx {, Vy} - Set Vx = Vy SHR 1, or Vx SHR 1 in place
 #[test]
 fn test_op_8xy6() {
-    check_math(0x0F, 0x01, 4, 0x1E, 0);
-    check_math(0xFF, 0xFF, 4, 0xFE, 1);
+    check_math(0x0F, 0x01, 6, 0x07, 1);
+    check_math(0xFE, 0xFF, 6, 0x7F, 0);
+    check_math_with(Quirks::COSMAC_VIP, 0x0F, 0x01, 6, 0x00, 1);
+    check_math_with(Quirks::COSMAC_VIP, 0x0F, 0xFE, 6, 0x7F, 0);
+}
+
+#[test]
+fn test_tick_leaves_timers() {
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&[0x12, 0x00]);
+    processor.delay_timer = 5;
+    processor.sound_timer = 5;
+    processor.tick([false; 16]).unwrap();
+    assert_eq!(processor.delay_timer, 5);
+    assert_eq!(processor.sound_timer, 5);
+}
+
+#[test]
+fn test_tick_timers() {
+    let mut processor = Processor::new(Quirks::default());
+    processor.delay_timer = 1;
+    processor.sound_timer = 0;
+    processor.tick_timers();
+    assert_eq!(processor.delay_timer, 0);
+    assert_eq!(processor.sound_timer, 0);
+}
+
+#[test]
+fn test_run_frame() {
+    let mut processor = Processor::new(Quirks::default());
+    let mut clock = Clock::new(600);
+    // Loop on a jump to itself so the PC stays put.
+    processor.load(&[0x12, 0x00]);
+    processor.delay_timer = 60;
+    for _ in 0..60 {
+        processor.run_frame([false; 16], &mut clock).unwrap();
+    }
+    assert_eq!(processor.delay_timer, 0);
+    assert_eq!(clock.cycles(), 600);
+    assert_eq!(processor.pc, 0x200);
+}
+
+#[test]
+fn test_run_frame_reports_draw() {
+    let mut processor = Processor::new(Quirks::default());
+    let mut clock = Clock::new(600);
+    processor.load(&[0xD0, 0x01, 0x12, 0x02]);
+    assert!(
+        processor
+            .run_frame([false; 16], &mut clock)
+            .unwrap()
+            .vram_changed
+    );
+    assert!(
+        !processor
+            .run_frame([false; 16], &mut clock)
+            .unwrap()
+            .vram_changed
+    );
+}
+
+#[test]
+fn test_sound_timeline() {
+    let mut processor = Processor::new(Quirks::default());
+    let mut clock = Clock::new(600);
+    // LD V0, 2; JP +0; LD ST, V0 at the third instruction of the frame.
+    processor.load(&[0x60, 0x02, 0x12, 0x04, 0xF0, 0x18, 0x12, 0x06]);
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    assert_eq!(
+        processor.take_sound(),
+        SoundSpan {
+            cycles: 10,
+            start_on: false,
+            events: vec![SoundEvent { cycle: 3, on: true }],
+        }
+    );
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    // The timer ran out on the second frame's timer tick, after all its
+    // instructions.
+    assert_eq!(
+        processor.take_sound(),
+        SoundSpan {
+            cycles: 10,
+            start_on: true,
+            events: vec![SoundEvent {
+                cycle: 10,
+                on: false,
+            }],
+        }
+    );
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    assert_eq!(processor.take_sound().events, []);
+}
+
+#[test]
+fn test_sound_timeline_across_reset() {
+    let mut processor = Processor::new(Quirks::default());
+    processor.sound_timer = 5;
+    processor.take_sound();
+    processor.reset();
+    assert_eq!(
+        processor.take_sound().events,
+        [SoundEvent {
+            cycle: 0,
+            on: false
+        }]
+    );
+}
+
+// SHL Vx {, Vy} - Set Vx = Vy SHL 1, or Vx SHL 1 in place
+#[test]
+fn test_op_8xye() {
+    check_math(0x81, 0x01, 0xE, 0x02, 1);
+    check_math(0x01, 0x81, 0xE, 0x02, 0);
+    check_math_with(Quirks::COSMAC_VIP, 0x01, 0x81, 0xE, 0x02, 1);
+    check_math_with(Quirks::COSMAC_VIP, 0x81, 0x01, 0xE, 0x02, 0);
+}
+
+#[test]
+fn test_default_quirks_are_legacy() {
+    assert_eq!(Quirks::default(), Quirks::LEGACY);
+    assert_eq!(Quirks::from_name("legacy"), Some(Quirks::LEGACY));
+    assert_eq!(Quirks::from_name("vip"), Some(Quirks::COSMAC_VIP));
+}
+
+#[test]
+fn test_quirk_vf_reset() {
+    for op in 1..=3 {
+        let mut processor = build_processor_with(Quirks::COSMAC_VIP);
+        processor.v[0xF] = 1;
+        processor.run_opcode(0x8010 + op).unwrap();
+        assert_eq!(processor.v[0xF], 0);
+
+        let mut processor = build_processor_with(Quirks::SUPER_CHIP);
+        processor.v[0xF] = 1;
+        processor.run_opcode(0x8010 + op).unwrap();
+        assert_eq!(processor.v[0xF], 1);
+    }
+}
+
+//JP V0, addr - Jump to location nnn + V0, or xnn + Vx
+#[test]
+fn test_op_bnnn() {
+    let mut processor = build_processor_with(Quirks::COSMAC_VIP);
+    processor.v[0] = 0x10;
+    processor.run_opcode(0xB520).unwrap();
+    assert_eq!(processor.pc, 0x530);
+
+    let mut processor = build_processor_with(Quirks::CHIP48);
+    processor.v[0] = 0x10;
+    processor.run_opcode(0xB520).unwrap();
+    assert_eq!(processor.pc, 0x522);
+}
+
+//LD [I], Vx - Store V0..Vx at I
+#[test]
+fn test_op_fx55() {
+    for &(quirks, i) in &[
+        (Quirks::COSMAC_VIP, 0x303),
+        (Quirks::CHIP48, 0x302),
+        (Quirks::SUPER_CHIP, 0x300),
+    ] {
+        let mut processor = build_processor_with(quirks);
+        processor.i = 0x300;
+        processor.run_opcode(0xF255).unwrap();
+        assert_eq!(processor.ram[0x300..0x304], [0, 0, 1, 0]);
+        assert_eq!(processor.i, i);
+    }
+}
+
+//LD Vx, [I] - Read V0..Vx from I
+#[test]
+fn test_op_fx65() {
+    for &(quirks, i) in &[
+        (Quirks::COSMAC_VIP, 0x303),
+        (Quirks::CHIP48, 0x302),
+        (Quirks::SUPER_CHIP, 0x300),
+    ] {
+        let mut processor = build_processor_with(quirks);
+        processor.i = 0x300;
+        processor.ram[0x300..0x304].copy_from_slice(&[9, 8, 7, 6]);
+        processor.run_opcode(0xF265).unwrap();
+        assert_eq!(processor.v[0..4], [9, 8, 7, 1]);
+        assert_eq!(processor.i, i);
+    }
+}
+
+fn draw_at_corner(quirks: Quirks) -> Processor {
+    let mut processor = build_processor_with(quirks);
+    processor.i = 0x300;
+    processor.ram[0x300] = 0xFF;
+    processor.ram[0x301] = 0xFF;
+    processor.v[0] = CHIP8_WIDTH as u8 - 4;
+    processor.v[1] = CHIP8_HEIGHT as u8 - 1;
+    processor.run_opcode(0xD012).unwrap();
+    processor
+}
+
+//DRW Vx, Vy, nibble - Display sprite at (Vx, Vy)
+#[test]
+fn test_op_dxyn_clips() {
+    let processor = draw_at_corner(Quirks::COSMAC_VIP);
+    let bottom = CHIP8_HEIGHT - 1;
+    assert_eq!(
+        processor.vram[bottom][CHIP8_WIDTH - 4..CHIP8_WIDTH],
+        [1, 1, 1, 1]
+    );
+    assert_eq!(processor.vram[bottom][0..4], [0, 0, 0, 0]);
+    assert_eq!(
+        processor.vram[0][CHIP8_WIDTH - 4..CHIP8_WIDTH],
+        [0, 0, 0, 0]
+    );
+}
+
+#[test]
+fn test_op_dxyn_wraps() {
+    let quirks = Quirks {
+        clip_sprites: false,
+        ..Quirks::COSMAC_VIP
+    };
+    let processor = draw_at_corner(quirks);
+    let bottom = CHIP8_HEIGHT - 1;
+    assert_eq!(
+        processor.vram[bottom][CHIP8_WIDTH - 4..CHIP8_WIDTH],
+        [1, 1, 1, 1]
+    );
+    assert_eq!(processor.vram[bottom][0..4], [1, 1, 1, 1]);
+    assert_eq!(
+        processor.vram[0][CHIP8_WIDTH - 4..CHIP8_WIDTH],
+        [1, 1, 1, 1]
+    );
+}
+
+#[test]
+fn test_op_dxyn_collision() {
+    let mut processor = draw_at_corner(Quirks::COSMAC_VIP);
+    assert_eq!(processor.v[0xF], 0);
+    processor.run_opcode(0xD012).unwrap();
+    assert_eq!(processor.v[0xF], 1);
+    assert_eq!(processor.vram[CHIP8_HEIGHT - 1][CHIP8_WIDTH - 1], 0);
+}
+
+#[test]
+fn test_initial_big_font() {
+    let processor = Processor::new(Quirks::default());
+    let addr = FONT_SET.len();
+    assert_eq!(processor.ram[addr..addr + 10], BIG_FONT_SET[0..10]);
+}
+
+//HIGH / LOW - Switch between 128x64 and 64x32
+#[test]
+fn test_op_00ff_00fe() {
+    let mut processor = build_processor();
+    processor.vram[0][0] = 1;
+    processor.run_opcode(0x00FF).unwrap();
+    assert_eq!(processor.width(), SCHIP_WIDTH);
+    assert_eq!(processor.height(), SCHIP_HEIGHT);
+    assert_eq!(processor.vram[0][0], 0);
+    assert!(processor.vram_changed);
+
+    processor.vram[0][0] = 1;
+    processor.run_opcode(0x00FE).unwrap();
+    assert_eq!(processor.width(), CHIP8_WIDTH);
+    assert_eq!(processor.height(), CHIP8_HEIGHT);
+    assert_eq!(processor.vram[0][0], 0);
+    assert_eq!(processor.pc, START_PC + 2 * OPCODE_SIZE);
+}
+
+//SCD nibble - Scroll down n lines
+#[test]
+fn test_op_00cn() {
+    let mut processor = build_processor();
+    processor.vram[0][3] = 1;
+    processor.vram[CHIP8_HEIGHT - 1][3] = 1;
+    processor.run_opcode(0x00C2).unwrap();
+    assert_eq!(processor.vram[0][3], 0);
+    assert_eq!(processor.vram[2][3], 1);
+    assert_eq!(processor.vram[CHIP8_HEIGHT - 1][3], 0);
+    assert_eq!(processor.pc, NEXT_PC);
+}
+
+//SCR / SCL - Scroll right or left by 4 pixels
+#[test]
+fn test_op_00fb_00fc() {
+    let mut processor = build_processor();
+    processor.run_opcode(0x00FF).unwrap();
+    processor.vram[5][0] = 1;
+    processor.vram[5][SCHIP_WIDTH - 1] = 1;
+    processor.run_opcode(0x00FB).unwrap();
+    assert_eq!(processor.vram[5][0..5], [0, 0, 0, 0, 1]);
+    assert_eq!(processor.vram[5][SCHIP_WIDTH - 1], 0);
+
+    processor.run_opcode(0x00FC).unwrap();
+    processor.run_opcode(0x00FC).unwrap();
+    assert_eq!(processor.vram[5][0..5], [0, 0, 0, 0, 0]);
+}
+
+//EXIT - Stop the interpreter
+#[test]
+fn test_op_00fd() {
+    let mut processor = Processor::new(Quirks::SUPER_CHIP);
+    processor.load(&[0x00, 0xFD, 0x60, 0x01]);
+    assert!(processor.tick([false; 16]).unwrap().exited);
+    processor.tick([false; 16]).unwrap();
+    assert_eq!(processor.v[0], 0);
+    assert_eq!(processor.pc, 0x202);
+}
+
+//DRW Vx, Vy, 0 - Display a 16x16 sprite
+#[test]
+fn test_op_dxy0() {
+    let mut processor = build_processor_with(Quirks::SUPER_CHIP);
+    processor.run_opcode(0x00FF).unwrap();
+    processor.i = 0x300;
+    for row in 0..16 {
+        processor.ram[0x300 + row * 2] = 0x80;
+        processor.ram[0x301 + row * 2] = 0x01;
+    }
+    processor.v[0] = 100;
+    processor.v[1] = 40;
+    processor.run_opcode(0xD010).unwrap();
+    for y in 40..56 {
+        assert_eq!(processor.vram[y][100], 1);
+        assert_eq!(processor.vram[y][101..115], [0; 14]);
+        assert_eq!(processor.vram[y][115], 1);
+    }
+    assert_eq!(processor.vram[56][100], 0);
+    assert_eq!(processor.v[0xF], 0);
+}
+
+//LD HF, Vx - Set I to the big font digit Vx
+#[test]
+fn test_op_fx30() {
+    let mut processor = build_processor();
+    processor.run_opcode(0xF430).unwrap();
+    assert_eq!(processor.i, FONT_SET.len() + 2 * 10);
+    assert_eq!(processor.pc, NEXT_PC);
+}
+
+//LD R, Vx / LD Vx, R - Save and restore RPL flags
+#[test]
+fn test_op_fx75_fx85() {
+    let mut processor = build_processor();
+    processor.run_opcode(0xF375).unwrap();
+    processor.v = [9; 16];
+    processor.run_opcode(0xF285).unwrap();
+    assert_eq!(processor.v[0..4], [0, 0, 1, 9]);
+}
+
+#[test]
+fn test_load_fills_xochip_memory() {
+    let mut processor = Processor::new(Quirks::XO_CHIP);
+    processor.load(&vec![7; XOCHIP_MEMORY]);
+    assert_eq!(processor.ram[XOCHIP_MEMORY - 1], 7);
+    assert_eq!(processor.ram[0x1FF], 0);
+}
+
+//LD I, long addr - Load a 16-bit address into I
+#[test]
+fn test_op_f000() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.ram[START_PC..START_PC + 4].copy_from_slice(&[0xF0, 0x00, 0xAB, 0xCD]);
+    processor.run_opcode(0xF000).unwrap();
+    assert_eq!(processor.i, 0xABCD);
+    assert_eq!(processor.pc, SKIPPED_PC);
+}
+
+#[test]
+fn test_skip_over_f000() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.ram[NEXT_PC..NEXT_PC + 2].copy_from_slice(&[0xF0, 0x00]);
+    processor.run_opcode(0x3201).unwrap();
+    assert_eq!(processor.pc, START_PC + 6);
+}
+
+//SAVE Vx - Vy / LOAD Vx - Vy - Store and read a register range without moving I
+#[test]
+fn test_op_5xy2_5xy3() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.i = 0x300;
+    processor.run_opcode(0x5362).unwrap();
+    assert_eq!(processor.ram[0x300..0x304], [1, 2, 2, 3]);
+    processor.run_opcode(0x5632).unwrap();
+    assert_eq!(processor.ram[0x300..0x304], [3, 2, 2, 1]);
+    assert_eq!(processor.i, 0x300);
+
+    processor.v = [0; 16];
+    processor.run_opcode(0x5133).unwrap();
+    assert_eq!(processor.v[0..5], [0, 3, 2, 2, 0]);
+    assert_eq!(processor.pc, START_PC + 3 * OPCODE_SIZE);
+}
+
+//PLANE n - Select drawing planes
+#[test]
+fn test_op_fn01_draws_both_planes() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.run_opcode(0xF301).unwrap();
+    processor.i = 0x300;
+    processor.ram[0x300] = 0x80;
+    processor.ram[0x301] = 0xC0;
+    processor.v[0] = 0;
+    processor.v[1] = 0;
+    processor.run_opcode(0xD011).unwrap();
+    assert_eq!(processor.vram[0][0..2], [3, 2]);
+    assert_eq!(processor.v[0xF], 0);
+
+    processor.run_opcode(0xF201).unwrap();
+    processor.run_opcode(0x00E0).unwrap();
+    assert_eq!(processor.vram[0][0..2], [1, 0]);
+}
+
+#[test]
+fn test_plane_collision() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.i = 0x300;
+    processor.ram[0x300] = 0x80;
+    processor.run_opcode(0xF201).unwrap();
+    processor.run_opcode(0xD011).unwrap();
+    processor.run_opcode(0xF101).unwrap();
+    processor.run_opcode(0xD011).unwrap();
+    assert_eq!(processor.v[0xF], 0);
+    processor.run_opcode(0xD011).unwrap();
+    assert_eq!(processor.v[0xF], 1);
+    assert_eq!(processor.vram[0][0], 2);
+}
+
+#[test]
+fn test_scroll_selected_plane() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.vram[0][0] = 3;
+    processor.run_opcode(0xF201).unwrap();
+    processor.run_opcode(0x00C1).unwrap();
+    assert_eq!(processor.vram[0][0], 1);
+    assert_eq!(processor.vram[1][0], 2);
+    processor.run_opcode(0x00D1).unwrap();
+    assert_eq!(processor.vram[0][0], 3);
+    assert_eq!(processor.vram[1][0], 0);
+}
+
+//AUDIO / PITCH Vx - Load the audio pattern and playback rate
+#[test]
+fn test_op_f002_fx3a() {
+    let mut processor = build_processor_with(Quirks::XO_CHIP);
+    processor.i = 0x300;
+    for (offset, byte) in processor.ram[0x300..0x310].iter_mut().enumerate() {
+        *byte = offset as u8;
+    }
+    processor.v[4] = 80;
+    processor.run_opcode(0xF002).unwrap();
+    processor.run_opcode(0xF43A).unwrap();
+    assert_eq!(processor.audio_pattern[15], 15);
+    assert_eq!(processor.pitch, 80);
+    let pc = processor.pc;
+    processor.ram[pc..pc + 2].copy_from_slice(&[0x1F, 0x04]);
+    let output = processor.tick([false; 16]).unwrap();
+    assert_eq!(output.audio_pattern[3], 3);
+    assert_eq!(output.pitch, 80);
+}
+
+fn fault(processor: &mut Processor, opcode: u16) -> ErrorKind {
+    let error = processor.run_opcode(opcode).unwrap_err();
+    assert_eq!(error.pc, START_PC);
+    assert_eq!(error.opcode, opcode);
+    assert_eq!(processor.pc, START_PC);
+    error.kind
+}
+
+#[test]
+fn test_stack_underflow() {
+    let mut processor = build_processor();
+    assert_eq!(fault(&mut processor, 0x00EE), ErrorKind::StackUnderflow);
+}
+
+#[test]
+fn test_stack_overflow() {
+    let mut processor = build_processor();
+    processor.sp = 16;
+    assert_eq!(fault(&mut processor, 0x2666), ErrorKind::StackOverflow);
+    assert_eq!(processor.sp, 16);
+}
+
+#[test]
+fn test_memory_out_of_range() {
+    let last = XOCHIP_MEMORY - 1;
+    let mut processor = build_processor();
+    processor.i = last;
+    assert_eq!(
+        fault(&mut processor, 0xF033),
+        ErrorKind::MemoryOutOfRange(XOCHIP_MEMORY)
+    );
+    assert_eq!(
+        fault(&mut processor, 0xF155),
+        ErrorKind::MemoryOutOfRange(XOCHIP_MEMORY)
+    );
+    assert_eq!(
+        fault(&mut processor, 0xF165),
+        ErrorKind::MemoryOutOfRange(XOCHIP_MEMORY)
+    );
+    assert_eq!(
+        fault(&mut processor, 0xD012),
+        ErrorKind::MemoryOutOfRange(XOCHIP_MEMORY)
+    );
+    assert_eq!(processor.v[1], 0);
+    assert_eq!(processor.i, last);
+}
+
+#[test]
+fn test_fetch_out_of_range() {
+    let mut processor = build_processor();
+    processor.pc = XOCHIP_MEMORY - 1;
+    let error = processor.tick([false; 16]).err().unwrap();
+    assert_eq!(error.kind, ErrorKind::MemoryOutOfRange(XOCHIP_MEMORY));
+    assert_eq!(error.pc, XOCHIP_MEMORY - 1);
+}
+
+#[test]
+fn test_invalid_key() {
+    let mut processor = build_processor();
+    processor.v[3] = 0x10;
+    assert_eq!(fault(&mut processor, 0xE39E), ErrorKind::InvalidKey(0x10));
+    assert_eq!(fault(&mut processor, 0xE3A1), ErrorKind::InvalidKey(0x10));
+}
+
+// SYS addr - Ignored
+#[test]
+fn test_op_0nnn() {
+    let mut processor = build_processor();
+    processor.run_opcode(0x0123).unwrap();
+    assert_eq!(processor.pc, NEXT_PC);
+}
+
+#[test]
+fn test_unknown_opcode() {
+    let mut processor = build_processor();
+    assert_eq!(fault(&mut processor, 0x5121), ErrorKind::UnknownOpcode);
+    assert_eq!(fault(&mut processor, 0xFFFF), ErrorKind::UnknownOpcode);
+}
+
+#[test]
+fn test_error_display() {
+    let mut processor = build_processor();
+    let error = processor.run_opcode(0x00EE).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "stack underflow (pc 0x0f00, opcode 00EE)"
+    );
+}
+
+#[test]
+fn test_save_state_round_trip() {
+    let mut processor = build_processor_with(Quirks::SUPER_CHIP);
+    processor.ram[0x300] = 0xAB;
+    processor.vram[63][127] = 3;
+    processor.hires = true;
+    processor.i = 0x1234;
+    processor.sp = 2;
+    processor.stack[1] = 0x10000;
+    processor.delay_timer = 9;
+    processor.sound_timer = 4;
+    processor.keypad[7] = true;
+    processor.keypad_waiting = true;
+    processor.keypad_register = 5;
+    processor.rpl[3] = 8;
+    processor.pitch = 99;
+    let state = processor.save_state();
+
+    let mut restored = Processor::new(Quirks::default());
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.quirks, Quirks::SUPER_CHIP);
+    assert_eq!(restored.ram[0x300], 0xAB);
+    assert_eq!(restored.vram[63][127], 3);
+    assert!(restored.hires);
+    assert_eq!(restored.v, processor.v);
+    assert_eq!(restored.i, 0x1234);
+    assert_eq!(restored.pc, START_PC);
+    assert_eq!(restored.stack[1], 0x10000);
+    assert_eq!((restored.delay_timer, restored.sound_timer), (9, 4));
+    assert!(restored.keypad[7]);
+    assert!(restored.keypad_waiting);
+    assert_eq!(restored.keypad_register, 5);
+    assert_eq!(restored.rpl[3], 8);
+    assert_eq!(restored.pitch, 99);
+    assert_eq!(restored.save_state(), state);
+}
+
+#[test]
+fn test_load_state_restores_random_position() {
+    let mut processor = build_processor();
+    processor.seed(42);
+    processor.run_opcode(0xC0FF).unwrap();
+    let state = processor.save_state();
+    processor.run_opcode(0xC0FF).unwrap();
+    let expected = processor.v[0];
+
+    processor.run_opcode(0xC0FF).unwrap();
+    processor.load_state(&state).unwrap();
+    processor.run_opcode(0xC0FF).unwrap();
+    assert_eq!(processor.v[0], expected);
+}
+
+#[test]
+fn test_load_state_rejects_corrupt_data() {
+    let processor = build_processor();
+    let mut state = processor.save_state();
+    let mut restored = Processor::new(Quirks::default());
+
+    assert!(matches!(
+        restored.load_state(&state[..state.len() - 1]),
+        Err(StateError::Truncated)
+    ));
+    state.push(0);
+    assert!(matches!(
+        restored.load_state(&state),
+        Err(StateError::Corrupt(_))
+    ));
+    state.pop();
+
+    // The first VRAM cell follows the header, the quirks and RAM.
+    let pixel = MAGIC.len() + 2 + 8 + 5 + XOCHIP_MEMORY;
+    state[pixel] = 4;
+    assert!(matches!(
+        restored.load_state(&state),
+        Err(StateError::Corrupt("pixel"))
+    ));
+    state[pixel] = 0;
+
+    restored.load(&[0x12, 0x00]);
+    assert!(matches!(
+        restored.load_state(&state),
+        Err(StateError::WrongRom)
+    ));
+    assert_eq!(restored.pc, 0x200);
+}
+
+#[test]
+fn test_op_cxkk_seeded() {
+    let mut first = build_processor();
+    let mut second = build_processor();
+    first.seed(42);
+    second.seed(42);
+    for _ in 0..8 {
+        first.run_opcode(0xC0F0).unwrap();
+        second.run_opcode(0xC0F0).unwrap();
+        assert_eq!(first.v[0], second.v[0]);
+        assert_eq!(first.v[0] & 0x0F, 0);
+    }
+}
+
+#[test]
+fn test_op_cxkk_scripted() {
+    let mut processor = build_processor();
+    processor.set_random_source(Box::new(Scripted::new(&[0xAB, 0x5C])));
+    processor.run_opcode(0xC0F0).unwrap();
+    assert_eq!(processor.v[0], 0xA0);
+    processor.run_opcode(0xC1FF).unwrap();
+    assert_eq!(processor.v[1], 0x5C);
+}
+
+#[test]
+fn test_reset() {
+    let mut processor = build_processor();
+    processor.load(&[0x00, 0xE0]);
+    processor.ram[0x300] = 0x42;
+    processor.rpl[0] = 7;
+    processor.vram[0][0] = 1;
+    processor.i = 0x300;
+    processor.sp = 1;
+    processor.delay_timer = 9;
+    processor.reset();
+    assert_eq!(processor.pc, PROGRAM_START);
+    assert_eq!(processor.ram[PROGRAM_START], 0x00);
+    assert_eq!(processor.ram[PROGRAM_START + 1], 0xE0);
+    assert_eq!(processor.ram[0x300], 0x42);
+    assert_eq!(processor.rpl[0], 7);
+    assert_eq!(processor.vram[0][0], 0);
+    assert_eq!(processor.v, [0; 16]);
+    assert_eq!(
+        (processor.i, processor.sp, processor.delay_timer),
+        (0, 0, 0)
+    );
 }
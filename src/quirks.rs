@@ -0,0 +1,94 @@
+/// How FX55/FX65 leave the index register once they're done.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// I is left unchanged (SUPER-CHIP).
+    Unchanged,
+    /// I is advanced by X (CHIP-48).
+    ByX,
+    /// I is advanced by X + 1 (COSMAC VIP).
+    ByXPlusOne,
+}
+
+/// Behaviors that differ between CHIP-8 interpreter variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY1/8XY2/8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    /// 8XY6/8XYE shift Vy into Vx instead of shifting Vx in place.
+    pub shift_uses_vy: bool,
+    /// What FX55/FX65 do to I.
+    pub index_increment: IndexIncrement,
+    /// BNNN jumps to XNN + VX instead of NNN + V0.
+    pub jump_uses_vx: bool,
+    /// DXYN clips sprites at the screen edge instead of wrapping them.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    /// How this interpreter has always behaved, and still does by default:
+    /// VF is left alone by the logic ops, shifts work on Vx in place, I is left
+    /// unchanged by FX55/FX65, BNNN adds V0 and sprites wrap.
+    pub const LEGACY: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::Unchanged,
+        jump_uses_vx: false,
+        clip_sprites: false,
+    };
+
+    /// The original COSMAC VIP interpreter.
+    pub const COSMAC_VIP: Quirks = Quirks {
+        vf_reset: true,
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        clip_sprites: true,
+    };
+
+    /// CHIP-48 on the HP-48 calculators.
+    pub const CHIP48: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::ByX,
+        jump_uses_vx: true,
+        clip_sprites: true,
+    };
+
+    /// SUPER-CHIP 1.1.
+    pub const SUPER_CHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::Unchanged,
+        jump_uses_vx: true,
+        clip_sprites: true,
+    };
+
+    /// XO-CHIP, as implemented by Octo.
+    pub const XO_CHIP: Quirks = Quirks {
+        vf_reset: false,
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        clip_sprites: false,
+    };
+
+    /// Looks up a preset by the name used on the command line.
+    pub fn from_name(name: &str) -> Option<Quirks> {
+        match name {
+            "legacy" => Some(Quirks::LEGACY),
+            "vip" | "chip8" => Some(Quirks::COSMAC_VIP),
+            "chip48" => Some(Quirks::CHIP48),
+            "schip" | "superchip" => Some(Quirks::SUPER_CHIP),
+            "xochip" => Some(Quirks::XO_CHIP),
+            _ => None,
+        }
+    }
+}
+
+/// `Quirks::LEGACY`, so programs keep running as they did before quirks were
+/// configurable; pick `COSMAC_VIP` or another preset to opt in.
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::LEGACY
+    }
+}
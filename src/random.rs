@@ -0,0 +1,110 @@
+use rand::rngs::OsRng;
+use rand::Rng;
+
+/// Where RND (CXKK) gets its bytes from. Sources are `Send`, so a `Processor`
+/// can be moved to another thread.
+pub trait RandomSource: Send {
+    fn next_byte(&mut self) -> u8;
+
+    /// Where the source is in its sequence, so save states and rewind can
+    /// put it back there; `None` for sources that can't be replayed.
+    fn position(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns to a position from `position`.
+    fn seek(&mut self, _position: u64) {}
+}
+
+/// A pseudo-random sequence that's the same every time for the same seed.
+///
+/// The generator is SplitMix64, kept here rather than taken from a crate so the
+/// sequence can't change under movies and seeds when dependencies are
+/// updated. Each byte is the top byte of the next 64-bit output.
+pub struct Seeded {
+    state: u64,
+}
+
+impl Seeded {
+    pub fn new(seed: u64) -> Self {
+        Seeded { state: seed }
+    }
+
+    // The next SplitMix64 output.
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl RandomSource for Seeded {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u64() >> 56) as u8
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.state)
+    }
+
+    fn seek(&mut self, position: u64) {
+        self.state = position;
+    }
+}
+
+/// Bytes from the operating system, different every session.
+#[derive(Default)]
+pub struct Entropy;
+
+impl Entropy {
+    pub fn new() -> Self {
+        Entropy
+    }
+}
+
+impl RandomSource for Entropy {
+    fn next_byte(&mut self) -> u8 {
+        OsRng.gen()
+    }
+}
+
+/// A fixed list of bytes, repeated once it runs out; an empty list gives
+/// zeroes. Lets tests choose exactly what RND returns.
+pub struct Scripted {
+    values: Vec<u8>,
+    next: usize,
+}
+
+impl Scripted {
+    pub fn new(values: &[u8]) -> Self {
+        Scripted {
+            values: values.to_vec(),
+            next: 0,
+        }
+    }
+}
+
+impl RandomSource for Scripted {
+    fn next_byte(&mut self) -> u8 {
+        if self.values.is_empty() {
+            return 0;
+        }
+        let value = self.values[self.next];
+        self.next = (self.next + 1) % self.values.len();
+        value
+    }
+
+    fn position(&self) -> Option<u64> {
+        Some(self.next as u64)
+    }
+
+    fn seek(&mut self, position: u64) {
+        self.next = position as usize % self.values.len().max(1);
+    }
+}
+
+#[cfg(test)]
+#[path = "./random_test.rs"]
+mod random_test;
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+use clock::TIMER_HZ;
+use processor::Processor;
+
+/// Frames between snapshots. Rewinding steps back one snapshot per frame, so
+/// it plays at this many times normal speed.
+pub const SNAPSHOT_INTERVAL: u32 = 2;
+
+/// How far back the buffer reaches by default.
+pub const DEFAULT_SECONDS: u32 = 60;
+
+/// A ring buffer of recent processor states for rewinding.
+///
+/// Only the newest snapshot is kept whole. Each older one is stored as the
+/// difference from the snapshot after it, and since a frame rarely touches
+/// more than a few registers, sprites and bytes of RAM, those differences are
+/// usually tiny. Rewinding pops the newest snapshot and applies the next
+/// difference to rebuild the one before; when the buffer is full the oldest
+/// difference is simply dropped.
+pub struct Rewind {
+    capacity: usize,
+    until_snapshot: u32,
+    newest: Option<Vec<u8>>,
+    // Oldest first; each turns the following snapshot into this one.
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_SECONDS)
+    }
+}
+
+impl Rewind {
+    /// A buffer reaching seconds of emulated time into the past.
+    pub fn new(seconds: u32) -> Self {
+        Rewind {
+            capacity: (seconds * TIMER_HZ / SNAPSHOT_INTERVAL).max(1) as usize,
+            until_snapshot: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Call once per emulated frame; takes a snapshot every
+    /// `SNAPSHOT_INTERVAL` frames.
+    pub fn record(&mut self, processor: &Processor) {
+        if self.until_snapshot == 0 {
+            self.push(processor.save_state());
+            self.until_snapshot = SNAPSHOT_INTERVAL;
+        }
+        self.until_snapshot -= 1;
+    }
+
+    /// Restores the newest snapshot and removes it, so the next call goes
+    /// further back. Returns false once the buffer is empty, or if the
+    /// snapshot can't be loaded, in which case the buffer is left as it was.
+    pub fn step_back(&mut self, processor: &mut Processor) -> bool {
+        let state = match self.newest {
+            Some(ref state) => state,
+            None => return false,
+        };
+        if processor.load_state(state).is_err() {
+            return false;
+        }
+        self.newest = self.deltas.pop_back().map(|delta| {
+            let mut older = state.clone();
+            apply(&mut older, &delta);
+            older
+        });
+        // Record a fresh snapshot as soon as the player lets go.
+        self.until_snapshot = 0;
+        true
+    }
+
+    /// Number of snapshots held.
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| 1 + self.deltas.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    /// Approximate memory used by the snapshots, in bytes.
+    pub fn size(&self) -> usize {
+        self.newest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.until_snapshot = 0;
+    }
+
+    fn push(&mut self, state: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            if newest.len() == state.len() {
+                self.deltas.push_back(diff(&state, &newest));
+                if self.deltas.len() >= self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                // Differences only work between states of the same layout.
+                self.deltas.clear();
+            }
+        }
+        self.newest = Some(state);
+    }
+}
+
+// Encodes the bytes that change from `from` to `to` as runs of
+// (unchanged count, changed count, XORed bytes), with counts as little-endian
+// u32s. Both states must be the same length.
+fn diff(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut position = 0;
+    while position < from.len() {
+        let start = position;
+        while position < from.len() && from[position] == to[position] {
+            position += 1;
+        }
+        let unchanged = position - start;
+        let start = position;
+        while position < from.len() && from[position] != to[position] {
+            position += 1;
+        }
+        delta.extend_from_slice(&(unchanged as u32).to_le_bytes());
+        delta.extend_from_slice(&((position - start) as u32).to_le_bytes());
+        delta.extend(
+            from[start..position]
+                .iter()
+                .zip(&to[start..position])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+    delta
+}
+
+// Turns `from` into `to` in place, given diff(from, to).
+fn apply(state: &mut [u8], delta: &[u8]) {
+    let mut position = 0;
+    let mut rest = delta;
+    while rest.len() >= 8 {
+        let unchanged = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let changed = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        position += unchanged;
+        for (byte, x) in state[position..position + changed]
+            .iter_mut()
+            .zip(&rest[8..])
+        {
+            *byte ^= x;
+        }
+        position += changed;
+        rest = &rest[8 + changed..];
+    }
+}
+
+#[cfg(test)]
+#[path = "./rewind_test.rs"]
+mod rewind_test;
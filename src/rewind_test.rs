@@ -0,0 +1,98 @@
+use super::*;
+use assembler::assemble;
+use clock::Clock;
+use Quirks;
+
+fn build_processor() -> Processor {
+    let mut processor = Processor::new(Quirks::default());
+    processor
+        .load(&assemble("LD I, #300\nloop: ADD V0, 1\nLD B, V0\nDRW V0, V1, 3\nJP loop").unwrap());
+    processor
+}
+
+#[test]
+fn test_diff_and_apply() {
+    let from = [1, 2, 3, 4, 5, 6];
+    let to = [1, 9, 9, 4, 5, 7];
+    let delta = diff(&from, &to);
+    let mut state = from;
+    apply(&mut state, &delta);
+    assert_eq!(state, to);
+    assert!(diff(&from, &from).len() <= 8);
+}
+
+#[test]
+fn test_step_back() {
+    let mut processor = build_processor();
+    let mut clock = Clock::new(60);
+    let mut rewind = Rewind::default();
+    let mut states = Vec::new();
+    for frame in 0..20 {
+        if frame % SNAPSHOT_INTERVAL == 0 {
+            states.push(processor.save_state());
+        }
+        rewind.record(&processor);
+        processor.run_frame([false; 16], &mut clock).unwrap();
+    }
+    assert_eq!(rewind.len(), 10);
+    // Deltas should be far smaller than whole states.
+    assert!(rewind.size() < 2 * states[0].len());
+
+    while let Some(expected) = states.pop() {
+        assert!(rewind.step_back(&mut processor));
+        assert_eq!(processor.save_state(), expected);
+    }
+    assert!(!rewind.step_back(&mut processor));
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn test_step_back_replays_random() {
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&assemble("loop: RND V0, #FF\nLD [I], V0\nJP loop").unwrap());
+    processor.seed(42);
+    let mut clock = Clock::new(60);
+    let mut rewind = Rewind::default();
+    rewind.record(&processor);
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    let expected = processor.save_state();
+
+    assert!(rewind.step_back(&mut processor));
+    processor.run_frame([false; 16], &mut clock).unwrap();
+    assert_eq!(processor.save_state(), expected);
+}
+
+#[test]
+fn test_capacity() {
+    let mut processor = build_processor();
+    let mut clock = Clock::new(60);
+    let mut rewind = Rewind::new(1);
+    for _ in 0..200 {
+        rewind.record(&processor);
+        processor.run_frame([false; 16], &mut clock).unwrap();
+    }
+    let capacity = (TIMER_HZ / SNAPSHOT_INTERVAL) as usize;
+    assert_eq!(rewind.len(), capacity);
+    let mut steps = 0;
+    while rewind.step_back(&mut processor) {
+        steps += 1;
+    }
+    assert_eq!(steps, capacity);
+}
+
+#[test]
+fn test_step_back_keeps_snapshots_it_cannot_load() {
+    let mut processor = build_processor();
+    let mut clock = Clock::new(60);
+    let mut rewind = Rewind::default();
+    for _ in 0..4 {
+        rewind.record(&processor);
+        processor.run_frame([false; 16], &mut clock).unwrap();
+    }
+    let mut other = Processor::new(Quirks::default());
+    other.load(&[0x12, 0x00]);
+    assert!(!rewind.step_back(&mut other));
+    assert_eq!(rewind.len(), 2);
+    assert!(rewind.step_back(&mut processor));
+    assert_eq!(rewind.len(), 1);
+}
@@ -0,0 +1,183 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use processor::Processor;
+
+pub const MAGIC: &[u8; 4] = b"C8SS";
+pub const VERSION: u16 = 3;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(io::Error),
+    /// The data doesn't start with the save state magic bytes.
+    NotAState,
+    /// The state was written by a newer, incompatible version.
+    UnsupportedVersion(u16),
+    /// The state was saved while a different ROM was loaded.
+    WrongRom,
+    /// The data ends before the state does.
+    Truncated,
+    /// A field holds a value no processor could be in.
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::Io(ref error) => write!(f, "{}", error),
+            StateError::NotAState => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::WrongRom => write!(f, "save state is for a different ROM"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Corrupt(field) => write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(error: io::Error) -> Self {
+        StateError::Io(error)
+    }
+}
+
+/// Appends little-endian fields to a state buffer, after a header naming the
+/// ROM (by `rom_hash`) the state belongs to.
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new(rom_hash: u64) -> Self {
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&rom_hash.to_le_bytes());
+        StateWriter { data }
+    }
+
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.data.extend_from_slice(value);
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// Reads back the fields written by `StateWriter`, in the same order.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    rom_hash: u64,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, StateError> {
+        if !data.starts_with(MAGIC) {
+            return Err(StateError::NotAState);
+        }
+        let mut reader = StateReader {
+            data: &data[MAGIC.len()..],
+            rom_hash: 0,
+        };
+        let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        reader.rom_hash = reader.u64()?;
+        Ok(reader)
+    }
+
+    /// The `rom_hash` of the ROM the state was saved with.
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    pub fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Corrupt("flag")),
+        }
+    }
+
+    pub fn u32(&mut self) -> Result<u32, StateError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        if self.data.len() < len {
+            return Err(StateError::Truncated);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    pub fn finish(self) -> Result<(), StateError> {
+        if self.data.is_empty() {
+            Ok(())
+        } else {
+            Err(StateError::Corrupt("length"))
+        }
+    }
+}
+
+/// Slot files sit next to the ROM: `pong.ch8` keeps slot 3 in `pong.ch8.state3`.
+pub fn slot_path(rom_path: &Path, slot: u8) -> PathBuf {
+    let mut name = rom_path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".state{}", slot));
+    rom_path.with_file_name(name)
+}
+
+pub fn save_slot(processor: &Processor, rom_path: &Path, slot: u8) -> Result<PathBuf, StateError> {
+    let path = slot_path(rom_path, slot);
+    fs::write(&path, processor.save_state())?;
+    Ok(path)
+}
+
+pub fn load_slot(
+    processor: &mut Processor,
+    rom_path: &Path,
+    slot: u8,
+) -> Result<PathBuf, StateError> {
+    let path = slot_path(rom_path, slot);
+    processor.load_state(&fs::read(&path)?)?;
+    Ok(path)
+}
+
+#[cfg(test)]
+#[path = "./savestate_test.rs"]
+mod savestate_test;
@@ -0,0 +1,79 @@
+use super::*;
+use std::env;
+use std::path::Path;
+use Quirks;
+
+#[test]
+fn test_slot_path() {
+    assert_eq!(
+        slot_path(Path::new("roms/pong.ch8"), 3),
+        Path::new("roms/pong.ch8.state3")
+    );
+}
+
+#[test]
+fn test_reader_rejects_bad_headers() {
+    assert!(matches!(
+        StateReader::new(b"PNG\0\0\0"),
+        Err(StateError::NotAState)
+    ));
+    assert!(matches!(
+        StateReader::new(b"C8SS\x09\x00"),
+        Err(StateError::UnsupportedVersion(9))
+    ));
+    assert!(matches!(
+        StateReader::new(b"C8SS\x01"),
+        Err(StateError::Truncated)
+    ));
+}
+
+#[test]
+fn test_writer_reader_round_trip() {
+    let mut writer = StateWriter::new(0xFEED);
+    writer.u8(7);
+    writer.bool(true);
+    writer.u32(0x12345678);
+    writer.u64(0x0123_4567_89AB_CDEF);
+    writer.bytes(&[1, 2, 3]);
+    let data = writer.finish();
+
+    let mut reader = StateReader::new(&data).unwrap();
+    assert_eq!(reader.rom_hash(), 0xFEED);
+    assert_eq!(reader.u8().unwrap(), 7);
+    assert!(reader.bool().unwrap());
+    assert_eq!(reader.u32().unwrap(), 0x12345678);
+    assert_eq!(reader.u64().unwrap(), 0x0123_4567_89AB_CDEF);
+    assert_eq!(reader.bytes(3).unwrap(), [1, 2, 3]);
+    assert!(reader.finish().is_ok());
+}
+
+#[test]
+fn test_save_and_load_slot() {
+    let dir = env::temp_dir().join(format!("rust-chip8-savestate-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rom_path = dir.join("test.ch8");
+
+    let mut processor = Processor::new(Quirks::default());
+    processor.load(&[0x60, 0x2A, 0x12, 0x02]);
+    processor.tick([false; 16]).unwrap();
+    let path = save_slot(&processor, &rom_path, 1).unwrap();
+    assert_eq!(path, dir.join("test.ch8.state1"));
+
+    let mut restored = Processor::new(Quirks::default());
+    restored.load(&[0x60, 0x2A, 0x12, 0x02]);
+    load_slot(&mut restored, &rom_path, 1).unwrap();
+    assert_eq!(restored.save_state(), processor.save_state());
+    assert!(matches!(
+        load_slot(&mut restored, &rom_path, 2),
+        Err(StateError::Io(_))
+    ));
+
+    let mut other = Processor::new(Quirks::default());
+    other.load(&[0x12, 0x00]);
+    assert!(matches!(
+        load_slot(&mut other, &rom_path, 1),
+        Err(StateError::WrongRom)
+    ));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,133 @@
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+
+use png;
+
+use Vram;
+
+/// RGB colours indexed by the two XO-CHIP plane bits; plain CHIP-8 only uses
+/// the first two.
+pub const PALETTE: [[u8; 3]; 4] = [[0, 0, 0], [255, 255, 255], [170, 170, 170], [85, 85, 85]];
+
+// ASCII art characters, indexed the same way.
+const ASCII: [char; 4] = ['.', '#', '+', '*'];
+
+/// File formats a framebuffer snapshot can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Indexed-colour PNG using `PALETTE`.
+    Png,
+    /// Plain (P1) PBM; any lit pixel is 1.
+    Pbm,
+    /// One character per pixel, one line per row.
+    Ascii,
+}
+
+impl Format {
+    /// Picks a format from a file extension; anything that isn't `.png` or
+    /// `.pbm` is written as ASCII art.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("png") => Format::Png,
+            Some(extension) if extension.eq_ignore_ascii_case("pbm") => Format::Pbm,
+            _ => Format::Ascii,
+        }
+    }
+}
+
+/// Writes the top-left width x height corner of vram.
+pub fn write<W: Write>(
+    out: W,
+    format: Format,
+    vram: &Vram,
+    width: usize,
+    height: usize,
+) -> io::Result<()> {
+    match format {
+        Format::Png => write_png(out, vram, width, height),
+        Format::Pbm => write_pbm(out, vram, width, height),
+        Format::Ascii => write_ascii(out, vram, width, height),
+    }
+}
+
+pub fn write_png<W: Write>(out: W, vram: &Vram, width: usize, height: usize) -> io::Result<()> {
+    write_scaled_png(out, vram, width, height, &PALETTE, 1)
+}
+
+/// Writes the top-left width x height corner of vram in palette's colours,
+/// each pixel scale pixels square.
+pub fn write_scaled_png<W: Write>(
+    out: W,
+    vram: &Vram,
+    width: usize,
+    height: usize,
+    palette: &[[u8; 3]; 4],
+    scale: usize,
+) -> io::Result<()> {
+    let (out_width, out_height) = (width * scale, height * scale);
+    let mut encoder = png::Encoder::new(out, out_width as u32, out_height as u32);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.concat());
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&indices(vram, width, height, out_width, out_height))?;
+    Ok(())
+}
+
+/// The top-left width x height corner of vram as palette indices, row by row,
+/// stretched or shrunk to out_width x out_height.
+pub fn indices(
+    vram: &Vram,
+    width: usize,
+    height: usize,
+    out_width: usize,
+    out_height: usize,
+) -> Vec<u8> {
+    (0..out_height)
+        .flat_map(|y| {
+            let row = &vram[y * height / out_height];
+            (0..out_width).map(move |x| row[x * width / out_width] & 0x3)
+        })
+        .collect()
+}
+
+pub fn write_pbm<W: Write>(mut out: W, vram: &Vram, width: usize, height: usize) -> io::Result<()> {
+    writeln!(out, "P1\n{} {}", width, height)?;
+    for row in rows(vram, width, height) {
+        let bits: Vec<&str> = row
+            .iter()
+            .map(|&pixel| if pixel != 0 { "1" } else { "0" })
+            .collect();
+        writeln!(out, "{}", bits.join(" "))?;
+    }
+    Ok(())
+}
+
+pub fn write_ascii<W: Write>(
+    mut out: W,
+    vram: &Vram,
+    width: usize,
+    height: usize,
+) -> io::Result<()> {
+    out.write_all(ascii(vram, width, height).as_bytes())
+}
+
+/// Renders the framebuffer as text, `#` for lit pixels and `.` for dark
+/// ones; the extra XO-CHIP colours are `+` and `*`.
+pub fn ascii(vram: &Vram, width: usize, height: usize) -> String {
+    let mut out = String::with_capacity((width + 1) * height);
+    for row in rows(vram, width, height) {
+        out.extend(row.iter().map(|&pixel| ASCII[pixel as usize & 0x3]));
+        out.push('\n');
+    }
+    out
+}
+
+fn rows(vram: &Vram, width: usize, height: usize) -> impl Iterator<Item = &[u8]> {
+    vram.iter().take(height).map(move |row| &row[..width])
+}
+
+#[cfg(test)]
+#[path = "./snapshot_test.rs"]
+mod snapshot_test;
@@ -0,0 +1,77 @@
+use super::*;
+use SCHIP_HEIGHT;
+use SCHIP_WIDTH;
+
+fn build_vram() -> Vram {
+    let mut vram = [[0; SCHIP_WIDTH]; SCHIP_HEIGHT];
+    vram[0][1] = 1;
+    vram[1][0] = 2;
+    vram[1][2] = 3;
+    vram
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(Format::from_path(Path::new("out.PNG")), Format::Png);
+    assert_eq!(Format::from_path(Path::new("out.pbm")), Format::Pbm);
+    assert_eq!(Format::from_path(Path::new("out.txt")), Format::Ascii);
+    assert_eq!(Format::from_path(Path::new("out")), Format::Ascii);
+}
+
+#[test]
+fn test_ascii() {
+    assert_eq!(ascii(&build_vram(), 3, 2), ".#.\n+.*\n");
+}
+
+#[test]
+fn test_pbm() {
+    let mut out = Vec::new();
+    write_pbm(&mut out, &build_vram(), 3, 2).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "P1\n3 2\n0 1 0\n1 0 1\n");
+}
+
+#[test]
+fn test_png() {
+    let mut out = Vec::new();
+    write_png(&mut out, &build_vram(), 3, 2).unwrap();
+
+    let decoder = png::Decoder::new(&out[..]);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!((info.width, info.height), (3, 2));
+    assert_eq!(info.color_type, png::ColorType::Indexed);
+    assert_eq!(&data[..info.buffer_size()], &[0, 1, 0, 2, 0, 3]);
+}
+
+#[test]
+fn test_scaled_png() {
+    let mut out = Vec::new();
+    let palette = [[1, 2, 3], [4, 5, 6], [7, 8, 9], [10, 11, 12]];
+    write_scaled_png(&mut out, &build_vram(), 2, 1, &palette, 2).unwrap();
+
+    let decoder = png::Decoder::new(&out[..]);
+    let mut reader = decoder.read_info().unwrap();
+    assert_eq!(
+        reader
+            .info()
+            .palette
+            .as_ref()
+            .map(|palette| palette.to_vec()),
+        Some(palette.concat())
+    );
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+    assert_eq!((info.width, info.height), (4, 2));
+    assert_eq!(&data[..info.buffer_size()], &[0, 0, 1, 1, 0, 0, 1, 1]);
+}
+
+#[test]
+fn test_indices_resize() {
+    // Doubled, then halved back.
+    let doubled = indices(&build_vram(), 3, 2, 6, 4);
+    assert_eq!(&doubled[..6], &[0, 0, 1, 1, 0, 0]);
+    assert_eq!(&doubled[12..18], &[2, 2, 0, 0, 3, 3]);
+    assert_eq!(indices(&build_vram(), 4, 2, 2, 1), [0, 0]);
+    assert_eq!(indices(&build_vram(), 3, 2, 3, 2), [0, 1, 0, 2, 0, 3]);
+}
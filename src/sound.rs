@@ -0,0 +1,372 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use config::{self, ConfigError};
+use processor::{SoundSpan, DEFAULT_AUDIO_PATTERN, DEFAULT_PITCH};
+
+pub const DEFAULT_FREQUENCY: f32 = 240.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+pub const MIN_FREQUENCY: f32 = 20.0;
+pub const MAX_FREQUENCY: f32 = 20000.0;
+/// The rate the beep is played, and recorded, at.
+pub const SAMPLE_RATE: u32 = 44100;
+
+// How long the beep takes to fade in and out, so it starts and stops without
+// a click.
+const ATTACK_SECONDS: f32 = 0.002;
+const RELEASE_SECONDS: f32 = 0.01;
+
+// How far behind the emulation the audio plays by default, to absorb the
+// jitter in when frames arrive.
+const LATENCY_SECONDS: f64 = 0.05;
+// Past this much queued audio, as when frames come faster than real time,
+// only the state each frame ends in is kept.
+const MAX_QUEUED_SECONDS: f64 = 0.25;
+// The audio device's clock never quite matches the host's, so the queue
+// slowly grows or drains. Each frame's stretch of timeline is squeezed or
+// stretched by this fraction per second the queue is off the latency, up to
+// the maximum, which moves beeps by too little to hear.
+const RATE_CORRECTION: f64 = 0.5;
+const MAX_RATE_CORRECTION: f64 = 0.02;
+// How far each frame moves the smoothed queue depth towards the measured
+// one, which jumps with every audio callback.
+const DEPTH_SMOOTHING: f64 = 0.05;
+
+/// The shape of the beep, for ROMs that don't load their own XO-CHIP audio
+/// pattern.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+    /// Random levels, changing twice per cycle of the frequency.
+    Noise,
+}
+
+impl Waveform {
+    pub const ALL: [Waveform; 4] = [
+        Waveform::Square,
+        Waveform::Sine,
+        Waveform::Triangle,
+        Waveform::Noise,
+    ];
+
+    pub fn parse(name: &str) -> Option<Waveform> {
+        Waveform::ALL
+            .iter()
+            .find(|waveform| waveform.name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Waveform::Square => "square",
+            Waveform::Sine => "sine",
+            Waveform::Triangle => "triangle",
+            Waveform::Noise => "noise",
+        }
+    }
+
+    /// The waveform after this one, wrapping around.
+    pub fn next(self) -> Waveform {
+        let index = Waveform::ALL.iter().position(|&w| w == self).unwrap_or(0);
+        Waveform::ALL[(index + 1) % Waveform::ALL.len()]
+    }
+}
+
+/// How the beep sounds, from `sound.toml` and the command line:
+///
+/// ```text
+/// frequency = 440
+/// waveform = "triangle"
+/// volume = 40
+/// muted = false
+/// ```
+///
+/// The volume is a percentage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundOptions {
+    /// Hz, from `MIN_FREQUENCY` to `MAX_FREQUENCY`.
+    pub frequency: f32,
+    pub waveform: Waveform,
+    /// From 0 (silent) to 1.
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for SoundOptions {
+    fn default() -> Self {
+        SoundOptions {
+            frequency: DEFAULT_FREQUENCY,
+            waveform: Waveform::Square,
+            volume: DEFAULT_VOLUME,
+            muted: false,
+        }
+    }
+}
+
+impl SoundOptions {
+    /// `sound.toml` in `config::config_dir`.
+    pub fn default_path() -> Option<PathBuf> {
+        config::config_dir().map(|dir| dir.join("sound.toml"))
+    }
+
+    /// Reads a config file; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<SoundOptions, ConfigError> {
+        match fs::read_to_string(path) {
+            Ok(text) => SoundOptions::parse(&text),
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(SoundOptions::default())
+            }
+            Err(error) => Err(ConfigError::Io(error)),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<SoundOptions, ConfigError> {
+        let mut options = SoundOptions::default();
+        for (index, line) in text.lines().enumerate() {
+            let invalid = |message: &str| ConfigError::Invalid(index + 1, message.to_string());
+            let line = config::strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid("expected <setting> = <value>"))?;
+            let value = value.trim();
+            match key.trim() {
+                "frequency" => {
+                    options.frequency = parse_frequency(value)
+                        .ok_or_else(|| invalid("frequency is a number of Hz from 20 to 20000"))?
+                }
+                "waveform" => {
+                    options.waveform = config::parse_string(value)
+                        .and_then(|name| Waveform::parse(&name))
+                        .ok_or_else(|| {
+                            invalid("expected \"square\", \"sine\", \"triangle\" or \"noise\"")
+                        })?
+                }
+                "volume" => {
+                    options.volume = parse_volume(value)
+                        .ok_or_else(|| invalid("volume is a percentage from 0 to 100"))?
+                }
+                "muted" => {
+                    options.muted = match value {
+                        "true" => true,
+                        "false" => false,
+                        _ => return Err(invalid("expected true or false")),
+                    }
+                }
+                _ => return Err(invalid("unknown setting")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Parses a frequency in Hz, within the audible range.
+pub fn parse_frequency(text: &str) -> Option<f32> {
+    text.parse()
+        .ok()
+        .filter(|hz| (MIN_FREQUENCY..=MAX_FREQUENCY).contains(hz))
+}
+
+/// Parses a percentage from 0 to 100 as a volume from 0 to 1.
+pub fn parse_volume(text: &str) -> Option<f32> {
+    text.parse::<f32>()
+        .ok()
+        .filter(|percent| (0.0..=100.0).contains(percent))
+        .map(|percent| percent / 100.0)
+}
+
+// XO-CHIP plays the pattern at 4000 * 2^((pitch - 64) / 48) bits per second.
+fn bit_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// Synthesises the beep: the configured tone, or the ROM's XO-CHIP pattern
+/// once it loads one, faded in and out as the sound timer starts and stops.
+///
+/// The emulator hands over the sound timer's timeline a frame at a time, and
+/// the beep starts and stops at the sample matching the instruction that
+/// changed the timer, a short latency later.
+pub struct Beeper {
+    sample_rate: f32,
+    options: SoundOptions,
+    pattern: [u8; 16],
+    pitch: u8,
+    on: bool,
+    // Beep changes still to play, by sample number, oldest first.
+    changes: VecDeque<(u64, bool)>,
+    // Samples played so far, and the sample the timeline is known up to.
+    played: u64,
+    queued: f64,
+    latency: f64,
+    // Seconds queued ahead of playback when frames arrive, smoothed.
+    depth: f64,
+    // Envelope level from 0 to 1.
+    level: f32,
+    // Through the tone's cycle from 0 to 1, or through the pattern's 128
+    // bits.
+    phase: f32,
+    noise: u16,
+    noise_level: f32,
+}
+
+impl Beeper {
+    pub fn new(options: SoundOptions, sample_rate: u32) -> Self {
+        Beeper {
+            sample_rate: sample_rate as f32,
+            options,
+            pattern: DEFAULT_AUDIO_PATTERN,
+            pitch: DEFAULT_PITCH,
+            on: false,
+            changes: VecDeque::new(),
+            played: 0,
+            queued: 0.0,
+            latency: LATENCY_SECONDS,
+            depth: LATENCY_SECONDS,
+            level: 0.0,
+            phase: 0.0,
+            noise: 1,
+            noise_level: 1.0,
+        }
+    }
+
+    pub fn options(&self) -> SoundOptions {
+        self.options
+    }
+
+    /// Changes the tone, volume or mute. The volume is clamped to 0 to 1 and
+    /// the frequency to the audible range.
+    pub fn set_options(&mut self, options: SoundOptions) {
+        self.options = SoundOptions {
+            frequency: options.frequency.clamp(MIN_FREQUENCY, MAX_FREQUENCY),
+            volume: options.volume.clamp(0.0, 1.0),
+            ..options
+        };
+    }
+
+    /// Sets how far behind the timeline the beep plays. Without a real-time
+    /// audio device, as when recording headless, it can be 0.
+    pub fn set_latency(&mut self, seconds: f64) {
+        self.latency = seconds;
+        self.depth = seconds;
+    }
+
+    /// Takes the ROM's XO-CHIP audio pattern and pitch. Until they differ from
+    /// the defaults, the configured tone plays instead.
+    pub fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        self.pattern = *pattern;
+        self.pitch = pitch;
+    }
+
+    /// Queues the next stretch of the sound timer's timeline, to be played
+    /// over this many seconds: a frame's worth, shorter when fast-forwarding.
+    pub fn push(&mut self, span: &SoundSpan, seconds: f32) {
+        let played = self.played as f64;
+        if self.queued <= played {
+            // Ran dry, after a pause or a slow frame: start again a latency
+            // from now.
+            self.queued = played + self.latency * self.sample_rate as f64;
+            self.depth = self.latency;
+            self.changes.clear();
+            self.on = false;
+        }
+        let start = self.queued;
+        if start - played > MAX_QUEUED_SECONDS * self.sample_rate as f64 {
+            let end_on = span.events.last().map_or(span.start_on, |event| event.on);
+            self.changes.push_back((start as u64, end_on));
+            return;
+        }
+
+        let depth = (start - played) / self.sample_rate as f64;
+        self.depth += (depth - self.depth) * DEPTH_SMOOTHING;
+        let correction = ((self.depth - self.latency) * RATE_CORRECTION)
+            .clamp(-MAX_RATE_CORRECTION, MAX_RATE_CORRECTION);
+        let samples = seconds as f64 * self.sample_rate as f64 * (1.0 - correction);
+        self.changes.push_back((start as u64, span.start_on));
+        for event in span.events.iter() {
+            let offset = if span.cycles == 0 {
+                samples
+            } else {
+                samples * event.cycle as f64 / span.cycles as f64
+            };
+            self.changes.push_back(((start + offset) as u64, event.on));
+        }
+        self.queued = start + samples;
+    }
+
+    /// Writes the next samples.
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let attack = 1.0 / (ATTACK_SECONDS * self.sample_rate);
+        let release = 1.0 / (RELEASE_SECONDS * self.sample_rate);
+        for sample in out.iter_mut() {
+            while let Some(&(at, on)) = self.changes.front() {
+                if at > self.played {
+                    break;
+                }
+                self.on = on;
+                self.changes.pop_front();
+            }
+            // Nothing is known past the timeline, so stop rather than beep on
+            // through a pause.
+            let on = self.on && (self.played as f64) < self.queued;
+            self.played += 1;
+
+            self.level = if on && !self.options.muted {
+                (self.level + attack).min(1.0)
+            } else {
+                (self.level - release).max(0.0)
+            };
+            if self.level == 0.0 {
+                // Start the next beep at the beginning of its wave.
+                self.phase = 0.0;
+                *sample = 0.0;
+                continue;
+            }
+            *sample = self.wave() * self.level * self.options.volume;
+        }
+    }
+
+    // The current point on the wave, from -1 to 1, and advances the phase.
+    fn wave(&mut self) -> f32 {
+        if self.pattern != DEFAULT_AUDIO_PATTERN || self.pitch != DEFAULT_PITCH {
+            let bit = self.phase as usize;
+            let high = self.pattern[bit / 8] >> (7 - bit % 8) & 1 == 1;
+            self.phase = (self.phase + bit_rate(self.pitch) / self.sample_rate) % 128.0;
+            return if high { 1.0 } else { -1.0 };
+        }
+
+        let phase = self.phase;
+        let value = match self.options.waveform {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Noise => self.noise_level,
+        };
+        self.phase += self.options.frequency / self.sample_rate;
+        if self.options.waveform == Waveform::Noise && (phase < 0.5) != (self.phase % 1.0 < 0.5) {
+            // A 15-bit LFSR, like the noise channels of old sound chips.
+            let bit = (self.noise ^ (self.noise >> 1)) & 1;
+            self.noise = (self.noise >> 1) | (bit << 14);
+            self.noise_level = if self.noise & 1 == 1 { 1.0 } else { -1.0 };
+        }
+        self.phase %= 1.0;
+        value
+    }
+}
+
+#[cfg(test)]
+#[path = "./sound_test.rs"]
+mod sound_test;
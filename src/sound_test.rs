@@ -0,0 +1,250 @@
+use super::*;
+use processor::SoundEvent;
+
+const SAMPLE_RATE: u32 = 8000;
+
+fn samples(beeper: &mut Beeper, count: usize) -> Vec<f32> {
+    let mut out = vec![0.0; count];
+    beeper.fill(&mut out);
+    out
+}
+
+fn loudest(samples: &[f32]) -> f32 {
+    samples
+        .iter()
+        .fold(0.0, |max: f32, sample| max.max(sample.abs()))
+}
+
+// Samples before a timeline handed to a fresh beeper starts playing.
+const LATENCY: usize = 400;
+
+// A span with the beep on at the start and changing at each (cycle, on).
+fn span(cycles: u32, start_on: bool, events: &[(u32, bool)]) -> SoundSpan {
+    SoundSpan {
+        cycles,
+        start_on,
+        events: events
+            .iter()
+            .map(|&(cycle, on)| SoundEvent { cycle, on })
+            .collect(),
+    }
+}
+
+// Queues a beep lasting seconds and plays up to its start.
+fn beep(beeper: &mut Beeper, seconds: f32) {
+    beeper.push(&span(1, true, &[]), seconds);
+    assert_eq!(loudest(&samples(beeper, LATENCY)), 0.0);
+}
+
+fn options(waveform: Waveform) -> SoundOptions {
+    SoundOptions {
+        frequency: 1000.0,
+        waveform,
+        volume: 1.0,
+        muted: false,
+    }
+}
+
+#[test]
+fn test_waveform_names() {
+    for &waveform in Waveform::ALL.iter() {
+        assert_eq!(Waveform::parse(waveform.name()), Some(waveform));
+    }
+    assert_eq!(Waveform::parse("Sine"), Some(Waveform::Sine));
+    assert_eq!(Waveform::parse("sawtooth"), None);
+    assert_eq!(Waveform::Noise.next(), Waveform::Square);
+}
+
+#[test]
+fn test_parse() {
+    let options = SoundOptions::parse(
+        "frequency = 440  # A\n\
+         waveform = \"triangle\"\n\
+         volume = 40\n\
+         muted = true\n",
+    )
+    .unwrap();
+    assert_eq!(options.frequency, 440.0);
+    assert_eq!(options.waveform, Waveform::Triangle);
+    assert_eq!(options.volume, 0.4);
+    assert!(options.muted);
+    assert_eq!(SoundOptions::parse("").unwrap(), SoundOptions::default());
+}
+
+#[test]
+fn test_parse_errors() {
+    let error = |text| match SoundOptions::parse(text) {
+        Err(ConfigError::Invalid(line, _)) => line,
+        other => panic!("expected an error, got {:?}", other),
+    };
+    assert_eq!(error("frequency = 5"), 1);
+    assert_eq!(error("\nwaveform = \"saw\""), 2);
+    assert_eq!(error("volume = 150"), 1);
+    assert_eq!(error("muted = 1"), 1);
+    assert_eq!(error("pitch = 3"), 1);
+}
+
+#[test]
+fn test_silent_until_on() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    assert_eq!(loudest(&samples(&mut beeper, 100)), 0.0);
+}
+
+#[test]
+fn test_attack_and_release() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    beep(&mut beeper, 0.125);
+    let start = samples(&mut beeper, 1000);
+    // Fades in over 2 ms rather than jumping to full volume.
+    assert!(start[0].abs() < 0.1);
+    assert_eq!(loudest(&start[20..]), 1.0);
+
+    let stop = samples(&mut beeper, 100);
+    assert!(stop[0].abs() > 0.9);
+    assert!(stop[40].abs() < 0.5);
+    assert_eq!(loudest(&stop[90..]), 0.0);
+}
+
+#[test]
+fn test_mute() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    beeper.set_options(SoundOptions {
+        muted: true,
+        ..beeper.options()
+    });
+    beep(&mut beeper, 0.125);
+    assert_eq!(loudest(&samples(&mut beeper, 100)), 0.0);
+}
+
+#[test]
+fn test_volume_is_clamped() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    beeper.set_options(SoundOptions {
+        volume: 2.0,
+        frequency: 1.0,
+        ..beeper.options()
+    });
+    assert_eq!(beeper.options().volume, 1.0);
+    assert_eq!(beeper.options().frequency, MIN_FREQUENCY);
+}
+
+#[test]
+fn test_waveforms() {
+    // 1 kHz at 8 kHz is 8 samples per cycle; skip past the attack.
+    let cycle = |waveform| {
+        let mut beeper = Beeper::new(options(waveform), SAMPLE_RATE);
+        beep(&mut beeper, 0.125);
+        samples(&mut beeper, 32)[24..].to_vec()
+    };
+    assert_eq!(
+        cycle(Waveform::Square),
+        [1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0, -1.0]
+    );
+    assert_eq!(
+        cycle(Waveform::Triangle),
+        [-1.0, -0.5, 0.0, 0.5, 1.0, 0.5, 0.0, -0.5]
+    );
+    let sine = cycle(Waveform::Sine);
+    assert!(sine[0].abs() < 0.001 && (sine[2] - 1.0).abs() < 0.001);
+    let noise = cycle(Waveform::Noise);
+    assert!(noise.iter().all(|&sample| sample.abs() == 1.0));
+}
+
+#[test]
+fn test_pattern_overrides_waveform() {
+    let mut beeper = Beeper::new(options(Waveform::Sine), SAMPLE_RATE);
+    beeper.set_pattern(&[0xFF; 16], DEFAULT_PITCH);
+    beep(&mut beeper, 0.125);
+    assert!(samples(&mut beeper, 100)[20..]
+        .iter()
+        .all(|&sample| sample == 1.0));
+}
+
+#[test]
+fn test_changes_land_on_their_sample() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    // On 3 instructions into a 10 instruction frame, off after 8.
+    beeper.push(&span(10, false, &[(3, true), (8, false)]), 0.125);
+    let out = samples(&mut beeper, LATENCY + 1000);
+    let frame = &out[LATENCY..];
+    assert_eq!(loudest(&frame[..300]), 0.0);
+    assert!(frame[300] != 0.0);
+    assert_eq!(loudest(&frame[320..800]), 1.0);
+    assert!(frame[800].abs() > 0.9 && frame[800].abs() < 1.0);
+    assert_eq!(loudest(&frame[900..]), 0.0);
+}
+
+#[test]
+fn test_frames_play_back_to_back() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    // A beep started at the end of one frame carries on through the next.
+    beeper.push(&span(4, false, &[(4, true)]), 0.125);
+    beeper.push(&span(4, true, &[]), 0.125);
+    let out = samples(&mut beeper, LATENCY + 2000);
+    assert_eq!(loudest(&out[..LATENCY + 1000]), 0.0);
+    assert_eq!(loudest(&out[LATENCY + 1020..]), 1.0);
+}
+
+#[test]
+fn test_restarts_after_running_dry() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    beep(&mut beeper, 0.125);
+    // Paused: the timeline runs out, and the beep stops.
+    assert_eq!(loudest(&samples(&mut beeper, 2000)[1100..]), 0.0);
+    // Resumed: the beep picks up again a latency later.
+    beep(&mut beeper, 0.125);
+    assert_eq!(loudest(&samples(&mut beeper, 1000)[20..]), 1.0);
+}
+
+#[test]
+fn test_no_latency() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    beeper.set_latency(0.0);
+    beeper.push(&span(1, true, &[]), 0.125);
+    assert_eq!(loudest(&samples(&mut beeper, 100)[20..]), 1.0);
+}
+
+#[test]
+fn test_queue_is_bounded() {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    for _ in 0..100 {
+        beeper.push(&span(1, false, &[]), 0.125);
+    }
+    beeper.push(&span(1, false, &[(1, true)]), 0.125);
+    let queued = (beeper.queued - beeper.played as f64) / SAMPLE_RATE as f64;
+    assert!(queued <= MAX_QUEUED_SECONDS + 0.125);
+    // The frames that didn't fit still leave the beep in their final state.
+    assert_eq!(beeper.changes.back(), Some(&(beeper.queued as u64, true)));
+}
+
+// Plays frames of timeline on a device whose clock runs at speed times the
+// host's, and returns the most and least queued seconds seen, after settling.
+fn drift(speed: f64) -> (f64, f64) {
+    let mut beeper = Beeper::new(options(Waveform::Square), SAMPLE_RATE);
+    let frame = SAMPLE_RATE as f64 / 60.0 * speed;
+    let mut due = 0.0;
+    let (mut most, mut least) = (0.0, f64::MAX);
+    for index in 0..6000 {
+        beeper.push(&span(1, false, &[]), 1.0 / 60.0);
+        due += frame;
+        let count = due as usize;
+        due -= count as f64;
+        samples(&mut beeper, count);
+        let queued = (beeper.queued - beeper.played as f64) / SAMPLE_RATE as f64;
+        if index >= 3000 {
+            most = queued.max(most);
+            least = queued.min(least);
+        }
+    }
+    (most, least)
+}
+
+#[test]
+fn test_rate_correction() {
+    // A 1% mismatch would fill the queue or run it dry within seconds.
+    for &speed in [0.99, 1.0, 1.01].iter() {
+        let (most, least) = drift(speed);
+        assert!(least > 0.0, "ran dry at speed {}", speed);
+        assert!(most < LATENCY_SECONDS + 0.03, "piled up at speed {}", speed);
+    }
+}
@@ -0,0 +1,62 @@
+use std::io;
+use std::io::prelude::*;
+use std::io::SeekFrom;
+
+// The RIFF header, "fmt " chunk and "data" chunk header before the samples.
+const HEADER_SIZE: u32 = 44;
+
+/// Writes 16-bit mono PCM audio as a WAV file. The sizes in the header are
+/// filled in by `finish`.
+pub struct WavWriter<W: Write + Seek> {
+    out: W,
+    // Bytes of samples written so far.
+    size: u32,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut out: W, sample_rate: u32) -> io::Result<Self> {
+        out.write_all(b"RIFF")?;
+        out.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        out.write_all(b"WAVEfmt ")?;
+        // Chunk size, PCM, one channel.
+        out.write_all(&16u32.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&1u16.to_le_bytes())?;
+        out.write_all(&sample_rate.to_le_bytes())?;
+        // Bytes per second, bytes per sample and bits per sample.
+        out.write_all(&(sample_rate * 2).to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?;
+        out.write_all(&16u16.to_le_bytes())?;
+        out.write_all(b"data")?;
+        out.write_all(&0u32.to_le_bytes())?;
+        Ok(WavWriter { out, size: 0 })
+    }
+
+    /// Adds samples from -1 to 1; anything louder is clipped.
+    pub fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(samples.len() * 2);
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        self.out.write_all(&bytes)?;
+        self.size += bytes.len() as u32;
+        Ok(())
+    }
+
+    /// Fills in the header's sizes and hands back the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(4))?;
+        self.out
+            .write_all(&(HEADER_SIZE - 8 + self.size).to_le_bytes())?;
+        self.out.seek(SeekFrom::Start(HEADER_SIZE as u64 - 4))?;
+        self.out.write_all(&self.size.to_le_bytes())?;
+        self.out.seek(SeekFrom::End(0))?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+#[path = "./wav_test.rs"]
+mod wav_test;
@@ -0,0 +1,43 @@
+use super::*;
+use std::io::Cursor;
+
+fn u32_at(bytes: &[u8], index: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[index],
+        bytes[index + 1],
+        bytes[index + 2],
+        bytes[index + 3],
+    ])
+}
+
+#[test]
+fn test_header() {
+    let wav = WavWriter::new(Cursor::new(Vec::new()), 44100)
+        .unwrap()
+        .finish()
+        .unwrap()
+        .into_inner();
+    assert_eq!(wav.len(), HEADER_SIZE as usize);
+    assert_eq!(&wav[..4], b"RIFF");
+    assert_eq!(u32_at(&wav, 4), 36);
+    assert_eq!(&wav[8..16], b"WAVEfmt ");
+    assert_eq!(u32_at(&wav, 24), 44100);
+    assert_eq!(u32_at(&wav, 28), 88200);
+    assert_eq!(&wav[36..40], b"data");
+    assert_eq!(u32_at(&wav, 40), 0);
+}
+
+#[test]
+fn test_samples() {
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), 8000).unwrap();
+    writer.write(&[0.0, 1.0, -1.0]).unwrap();
+    writer.write(&[0.5, 2.0]).unwrap();
+    let wav = writer.finish().unwrap().into_inner();
+    assert_eq!(u32_at(&wav, 4), 36 + 10);
+    assert_eq!(u32_at(&wav, 40), 10);
+    let samples: Vec<i16> = wav[44..]
+        .chunks(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    assert_eq!(samples, [0, 32767, -32767, 16384, 32767]);
+}
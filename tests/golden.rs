@@ -0,0 +1,94 @@
+//! Runs ROMs headlessly and compares the final framebuffer with the ASCII art
+//! in tests/golden.
+//!
+//! ROMs come from tests/roms, either assembled from `<name>.asm` or loaded
+//! from `<name>.ch8`. Run with `BLESS=1` to write the golden images from the
+//! current output, then review the diff.
+
+extern crate rust_chip8;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rust_chip8::assembler;
+use rust_chip8::headless::{self, KeyScript, Limit};
+use rust_chip8::snapshot;
+use rust_chip8::{Clock, Processor, Quirks};
+
+fn tests_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn load_rom(name: &str) -> Vec<u8> {
+    let roms = tests_dir().join("roms");
+    let source = roms.join(format!("{}.asm", name));
+    if source.exists() {
+        return assembler::assemble_file(&source).unwrap_or_else(|error| panic!("{}", error));
+    }
+    fs::read(roms.join(format!("{}.ch8", name)))
+        .unwrap_or_else(|error| panic!("tests/roms/{}.ch8: {}", name, error))
+}
+
+fn check(golden: &str, rom: &str, quirks: Quirks, frames: u64) {
+    let rom = load_rom(rom);
+    let mut processor = Processor::new(quirks);
+    processor.load(&rom);
+    headless::run(
+        &mut processor,
+        &mut Clock::default(),
+        Limit::Frames(frames),
+        &KeyScript::default(),
+    )
+    .unwrap_or_else(|error| panic!("{}: {}", golden, error));
+    let actual = snapshot::ascii(processor.vram(), processor.width(), processor.height());
+
+    let path = tests_dir().join("golden").join(format!("{}.txt", golden));
+    if env::var_os("BLESS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "{} has no golden image; run with BLESS=1 to create it",
+            golden
+        )
+    });
+    assert!(
+        actual == expected,
+        "{} does not match {}; the framebuffer was:\n{}",
+        golden,
+        path.display(),
+        actual
+    );
+}
+
+#[test]
+fn arithmetic_vip() {
+    check("arithmetic-vip", "arithmetic", Quirks::COSMAC_VIP, 60);
+}
+
+#[test]
+fn arithmetic_chip48() {
+    check("arithmetic-chip48", "arithmetic", Quirks::CHIP48, 60);
+}
+
+#[test]
+fn clipping_vip() {
+    check("clipping-vip", "clipping", Quirks::COSMAC_VIP, 60);
+}
+
+#[test]
+fn clipping_xochip() {
+    check("clipping-xochip", "clipping", Quirks::XO_CHIP, 60);
+}
+
+#[test]
+fn hires_font() {
+    check("hires-font", "hires-font", Quirks::SUPER_CHIP, 60);
+}
+
+#[test]
+fn planes() {
+    check("planes", "planes", Quirks::XO_CHIP, 60);
+}
@@ -0,0 +1,64 @@
+; Prints the result (V0) and flag (VF) of each ALU operation as hex digits,
+; four operations to a row.
+
+    LD V8, 0
+    LD V9, 0
+
+    LD V0, #F0
+    LD V1, #0F
+    ADD V0, V1          ; FF 0
+    CALL print
+    LD V0, #F0
+    LD V1, #20
+    ADD V0, V1          ; 10 1
+    CALL print
+    LD V0, #42
+    LD V1, #42
+    SUB V0, V1          ; 00 1, equal values don't borrow
+    CALL print
+    LD V0, #10
+    LD V1, #20
+    SUB V0, V1          ; F0 0
+    CALL print
+    LD V0, #42
+    LD V1, #42
+    SUBN V0, V1         ; 00 1
+    CALL print
+    LD V0, #81
+    SHR V0              ; 40 1
+    CALL print
+    LD V0, #81
+    SHL V0              ; 02 1
+    CALL print
+    LD V0, #0F
+    LD VF, #07
+    LD V1, #F0
+    OR V0, V1           ; FF, VF is reset to 0 only with the vf_reset quirk
+    CALL print
+done:
+    JP done
+
+; Draws V0 and VF at (V8, V9) and moves on to the next slot.
+print:
+    LD V4, VF
+    LD V2, V0
+    SHR V2
+    SHR V2
+    SHR V2
+    SHR V2
+    LD F, V2
+    DRW V8, V9, 5
+    ADD V8, 5
+    LD V3, #0F
+    AND V3, V0
+    LD F, V3
+    DRW V8, V9, 5
+    ADD V8, 6
+    LD F, V4
+    DRW V8, V9, 5
+    ADD V8, 5
+    SE V8, 64
+    RET
+    LD V8, 0
+    ADD V9, 7
+    RET
@@ -0,0 +1,16 @@
+; Draws a box across the bottom-right corner, which clips or wraps depending
+; on the clip_sprites quirk, and one starting past the right edge, whose
+; coordinates always wrap.
+
+    LD I, box
+    LD V0, 60
+    LD V1, 28
+    DRW V0, V1, 8
+    LD V0, 70
+    LD V1, 10
+    DRW V0, V1, 8
+done:
+    JP done
+
+box:
+    db #FF, #81, #81, #81, #81, #81, #81, #FF
@@ -0,0 +1,18 @@
+; Draws the big SUPER-CHIP digits above the small ones in high resolution.
+
+    HIGH
+    LD V0, 0            ; digit
+    LD V1, 0            ; x
+loop:
+    LD HF, V0
+    LD V2, 0
+    DRW V1, V2, 10
+    LD F, V0
+    LD V2, 12
+    DRW V1, V2, 5
+    ADD V0, 1
+    ADD V1, 8
+    SE V0, 16
+    JP loop
+done:
+    JP done
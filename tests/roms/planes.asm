@@ -0,0 +1,22 @@
+; Draws to each XO-CHIP bitplane, and to both at once, so every colour shows.
+
+    LD I, box
+    PLANE 1
+    LD V0, 0
+    DRW V0, V0, 8
+    PLANE 2
+    LD V0, 4
+    DRW V0, V0, 8
+    PLANE 3
+    LD I, stripes
+    LD V0, 20
+    LD V1, 0
+    DRW V0, V1, 4       ; 4 rows for plane 1, then 4 for plane 2
+done:
+    JP done
+
+box:
+    db #FF, #81, #81, #81, #81, #81, #81, #FF
+stripes:
+    db #F0, #F0, #F0, #F0
+    db #FF, #00, #FF, #00